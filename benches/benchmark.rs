@@ -185,6 +185,21 @@ fn iteration(c: &mut Criterion) {
                 }
             })
         });
+        g.bench_with_input(format!("FuseBox_grouped_n{n}"), &n, |b, &n| {
+            let mut f = prepare_fused(n);
+            f.sort_by_type();
+
+            b.iter(|| {
+                f.for_each_group_mut(|_, group| {
+                    for v in group {
+                        v.calculate()
+                    }
+                });
+                for v in f.iter() {
+                    black_box(v.get_result());
+                }
+            })
+        });
     }
     g.finish();
 }
@@ -1,25 +1,76 @@
-use iter::{Iter, IterMut};
-use std::{
-    alloc::{alloc, dealloc, Layout},
-    marker::Unsize,
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+use alloc::alloc::dealloc;
+#[cfg(feature = "alloc")]
+use alloc::{
+    alloc::{alloc, handle_alloc_error},
+    boxed::Box,
+    collections::BTreeMap,
+    vec::Vec,
+};
+#[cfg(feature = "alloc")]
+use bytes::RawBytes;
+#[cfg(feature = "alloc")]
+use compact::Compactor;
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+use core::marker::Unsize;
+use core::ops::{Bound, Range, RangeBounds};
+#[cfg(feature = "alloc")]
+use core::{
+    alloc::Layout,
+    cmp::Ordering,
+    mem::{self, ManuallyDrop, MaybeUninit},
     ops::{Index, IndexMut},
-    ptr::{self, drop_in_place, NonNull, Pointee},
+    ptr::{self, drop_in_place},
+    slice::GetDisjointMutError,
 };
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use handle::Handle;
+#[cfg(feature = "alloc")]
+use iter::{Chunks, ChunksMut, Drain, ExtractIf, IntoIter, Iter, IterMut, PairsMut, Windows};
+use meta::Metadata;
+#[cfg(feature = "alloc")]
+use slice::{FuseSlice, FuseSliceMut};
 
+pub mod array;
+#[cfg(feature = "alloc")]
+mod bytes;
+#[cfg(feature = "alloc")]
+mod compact;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod handle;
 pub mod iter;
+pub mod meta;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod slab;
+pub mod slice;
+#[cfg(feature = "alloc")]
+pub mod small;
+#[cfg(feature = "alloc")]
+pub mod stride;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod test;
 
-#[derive(Clone, Copy)]
-struct Header<Dyn>
+pub(crate) struct Header<Dyn>
 where
     Dyn: ?Sized,
 {
-    offset: usize,
-    meta: <Dyn as Pointee>::Metadata,
+    pub(crate) offset: usize,
+    pub(crate) meta: Metadata<Dyn>,
 }
 
+impl<Dyn> Clone for Header<Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Dyn> Copy for Header<Dyn> where Dyn: ?Sized {}
+
+#[cfg(feature = "alloc")]
 /// Contigous type-erased append-only vector
 ///
 /// `Dyn` shall be `dyn Trait`
@@ -28,13 +79,12 @@ where
     Dyn: ?Sized,
 {
     headers: Vec<Header<Dyn>>,
-    inner: NonNull<u8>,
-    last_size: usize,
-    max_align: usize,
-    len_bytes: usize,
-    cap_bytes: usize,
+    bytes: RawBytes,
+    /// Shared by all live handles, 0 if there are none.
+    stamp: usize,
 }
 
+#[cfg(feature = "alloc")]
 impl<Dyn> Default for FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -44,28 +94,24 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<Dyn> Drop for FuseBox<Dyn>
 where
     Dyn: ?Sized,
 {
     fn drop(&mut self) {
-        if self.cap_bytes != 0 {
-            // Safety:
-            // inner guaranteed to be valid here
-            // values are guaranteed to be aligned
-            unsafe {
-                for val in self.iter_mut() {
-                    drop_in_place(val);
-                }
-                dealloc(
-                    self.inner.as_ptr(),
-                    Layout::from_size_align_unchecked(self.cap_bytes, self.max_align),
-                );
+        // Safety:
+        // inner guaranteed to be valid here
+        // values are guaranteed to be aligned
+        unsafe {
+            for val in self.iter_mut() {
+                drop_in_place(val);
             }
         }
     }
 }
 
+#[cfg(feature = "alloc")]
 unsafe impl<Dyn> Send for FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -73,6 +119,7 @@ where
 {
 }
 
+#[cfg(feature = "alloc")]
 unsafe impl<Dyn> Sync for FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -80,6 +127,7 @@ where
 {
 }
 
+#[cfg(feature = "alloc")]
 impl<Dyn> FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -89,11 +137,8 @@ where
     pub fn new() -> Self {
         Self {
             headers: Vec::new(),
-            inner: std::ptr::NonNull::dangling(),
-            last_size: 0,
-            max_align: 0,
-            len_bytes: 0,
-            cap_bytes: 0,
+            bytes: RawBytes::new(),
+            stamp: 0,
         }
     }
 
@@ -110,106 +155,461 @@ where
         self.len() == 0
     }
 
+    /// Bitwise moves a value described by `layout` and `meta` from `src` into the [`FuseBox`].
+    ///
+    /// # Safety
+    /// `src` must point to a valid value of that layout and metadata,
+    /// which the caller must not use or drop afterwards.
     #[inline]
-    fn realloc(&mut self, min_layout: Layout) {
-        if self.cap_bytes == 0 {
-            self.max_align = min_layout.align();
-            unsafe {
-                self.cap_bytes = min_layout.pad_to_align().size();
-                let new = alloc(min_layout);
-                if new.is_null() {
-                    panic!(
-                        "Failed to allocate memory for {}",
-                        std::any::type_name::<Self>()
-                    )
-                }
-                self.inner = NonNull::new_unchecked(new);
-            }
-            return;
-        }
+    pub(crate) unsafe fn push_raw(&mut self, src: *const u8, layout: Layout, meta: Metadata<Dyn>) {
+        let header = self.make_header(layout, meta);
+        let offset = header.offset;
 
-        let old = self.inner;
-        let old_layout =
-            unsafe { Layout::from_size_align_unchecked(self.cap_bytes, self.max_align) };
-        if self.max_align < min_layout.align() {
-            self.max_align = min_layout.align();
+        if (layout.size() != 0 || layout.align() > 1)
+            && (self.bytes.cap_bytes.saturating_sub(offset) < layout.size()
+                || layout.align() > self.bytes.max_align)
+        {
+            self.bytes.realloc(layout);
         }
-        let size = if self.cap_bytes == 0 {
-            min_layout.size()
-        } else {
-            self.cap_bytes
-                .checked_mul(2)
-                .and_then(|s| s.checked_add(min_layout.size()))
-                .expect("New capacity overflowed usize")
-        };
+
+        // Safety: offset guaranteed to be in-bounds
         unsafe {
-            let layout = Layout::from_size_align_unchecked(size, self.max_align).pad_to_align();
-            self.cap_bytes = layout.size();
-            let new = alloc(layout);
-            std::ptr::copy(old.as_ptr(), new, self.len_bytes);
-            self.inner = NonNull::new_unchecked(new);
-            dealloc(old.as_ptr(), old_layout);
+            ptr::copy_nonoverlapping(src, self.bytes.inner.as_ptr().add(offset), layout.size());
+        }
+        self.headers.push(header);
+        self.bytes.len_bytes = offset + layout.size();
+    }
+
+    #[inline]
+    fn make_header(&mut self, layout: Layout, meta: Metadata<Dyn>) -> Header<Dyn> {
+        Header {
+            offset: round_up(self.bytes.len_bytes, layout.align()),
+            meta,
         }
     }
 
+    #[cfg(feature = "nightly")]
     #[inline]
-    unsafe fn push_unsafe<T>(&mut self, v: T)
+    /// Appends an element to the vector.
+    pub fn push<T>(&mut self, v: T)
     where
         T: 'static,
         T: Unsize<Dyn>,
+        Dyn: 'static,
     {
-        let as_dyn: &Dyn = &v;
-        let meta = ptr::metadata(as_dyn);
-        let layout = Layout::new::<T>();
-        let header = self.make_header(layout, meta);
-        let offset = header.offset;
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce(v, |p| p) }
+    }
 
-        if layout.size() == 0 && layout.align() <= 1 {
-            // Safety: offset guaranteed to be in-bounds
-            unsafe { self.inner.as_ptr().add(offset).cast::<T>().write(v) }
-            self.headers.push(header);
-        } else {
-            if self.cap_bytes.saturating_sub(offset) < layout.size()
-                || layout.align() > self.max_align
-            {
-                self.realloc(layout);
-            }
+    #[inline]
+    /// Appends an element to the vector, using `coerce` to unsize it.
+    ///
+    /// Without the `nightly` feature this is the only way to push,
+    /// use [`fuse_push!`](crate::fuse_push) to call it safely.
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce<T>(&mut self, v: T, coerce: fn(*mut T) -> *mut Dyn)
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        let meta = unsafe { meta::from_coerce(coerce) };
+        let v = ManuallyDrop::new(v);
+        unsafe { self.push_raw((&*v as *const T).cast(), Layout::new::<T>(), meta) }
+    }
 
-            unsafe { self.inner.as_ptr().add(offset).cast::<T>().write(v) }
-            self.headers.push(header);
+    #[cfg(all(feature = "nightly", target_has_atomic = "ptr"))]
+    #[inline]
+    /// Appends an element to the vector, returning a typed [`Handle`] to it.
+    pub fn push_handle<T>(&mut self, v: T) -> Handle<T>
+    where
+        T: 'static,
+        T: Unsize<Dyn>,
+        Dyn: 'static,
+    {
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce_handle(v, |p| p) }
+    }
+
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    /// Appends an element to the vector using `coerce` to unsize it,
+    /// returning a typed [`Handle`] to it.
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce_handle<T>(
+        &mut self,
+        v: T,
+        coerce: fn(*mut T) -> *mut Dyn,
+    ) -> Handle<T>
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        unsafe { self.push_coerce(v, coerce) };
+        if self.stamp == 0 {
+            self.stamp = handle::next_stamp();
         }
-        self.last_size = layout.size();
-        self.len_bytes = offset + layout.size();
+        Handle::new(self.len() - 1, self.stamp)
     }
 
+    #[cfg(target_has_atomic = "ptr")]
+    #[must_use]
     #[inline]
-    fn make_header(&mut self, layout: Layout, meta: <Dyn as Pointee>::Metadata) -> Header<Dyn> {
-        if self.is_empty() {
-            Header { offset: 0, meta }
-        } else {
-            let Header { offset, meta: _ } = self.headers[self.len() - 1];
-            let offset = round_up(offset + self.last_size, layout.align());
-            Header { offset, meta }
+    /// Returns `true` if `handle` was issued by this [`FuseBox`] and is still valid.
+    pub fn is_valid<T>(&self, handle: Handle<T>) -> bool {
+        self.stamp != 0 && handle.stamp == self.stamp
+    }
+
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    fn handle_ptr<T>(&self, handle: Handle<T>) -> *mut T {
+        assert!(self.is_valid(handle), "invalid handle");
+        // Safety: valid handles point at a value of type T
+        unsafe {
+            self.bytes
+                .inner
+                .as_ptr()
+                .add(self.headers[handle.index].offset)
+                .cast()
         }
     }
 
+    #[cfg(target_has_atomic = "ptr")]
+    #[must_use]
     #[inline]
-    /// Appends an element to the vector.
-    pub fn push<T>(&mut self, v: T)
+    /// Retrieves the element behind `handle` as `&Dyn`.
+    ///
+    /// # Panics
+    /// Panics if `handle` is not valid for this [`FuseBox`].
+    pub fn dyn_ref<T>(&self, handle: Handle<T>) -> &Dyn {
+        assert!(self.is_valid(handle), "invalid handle");
+        unsafe { &*self.get_raw(handle.index) }
+    }
+
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    /// Retrieves the element behind `handle` as `&mut Dyn`.
+    ///
+    /// # Panics
+    /// Panics if `handle` is not valid for this [`FuseBox`].
+    pub fn dyn_mut<T>(&mut self, handle: Handle<T>) -> &mut Dyn {
+        assert!(self.is_valid(handle), "invalid handle");
+        unsafe { &mut *self.get_raw(handle.index) }
+    }
+
+    /// Moves every boxed value into the [`FuseBox`], freeing the boxes.
+    ///
+    /// This can't be an [`Extend`] impl, as it would overlap with `Extend<T>`.
+    #[cfg(feature = "nightly")]
+    pub fn extend_boxed<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Box<Dyn>>,
+    {
+        for b in iter {
+            self.push_box(b)
+        }
+    }
+
+    /// Moves every value into its own [`Box`].
+    #[must_use]
+    pub fn into_boxed_vec(self) -> Vec<Box<Dyn>> {
+        self.into_iter().collect()
+    }
+
+    #[cfg(feature = "nightly")]
+    #[inline]
+    /// Moves an already boxed value to the end of the vector and frees the box.
+    pub fn push_box(&mut self, b: Box<Dyn>) {
+        let layout = Layout::for_value(&*b);
+        let raw = Box::into_raw(b);
+        // Safety: the value is moved out of the box, which is then freed without dropping it
+        unsafe {
+            self.push_raw(raw.cast(), layout, meta::from_ptr(raw));
+            if layout.size() != 0 {
+                dealloc(raw.cast(), layout);
+            }
+        }
+    }
+
+    /// Removes the element at `n` and moves it into its own [`Box`],
+    /// shifting later elements down.
+    ///
+    /// # Panics
+    /// Panics if `n` is out of bounds.
+    pub fn take(&mut self, n: usize) -> Box<Dyn> {
+        assert!(n < self.len(), "index out of bounds");
+        let Header { offset, meta } = self.headers[n];
+        let layout = self.layout_of(n);
+        // Safety: the value is moved out before its header is removed
+        unsafe {
+            let b = move_to_box(self.bytes.inner.as_ptr().add(offset), layout, meta);
+            self.remove_raw(n);
+            b
+        }
+    }
+
+    /// Removes the last element and moves it into its own [`Box`],
+    /// or returns `None` if the [`FuseBox`] is empty.
+    pub fn take_last(&mut self) -> Option<Box<Dyn>> {
+        let n = self.len().checked_sub(1)?;
+        Some(self.take(n))
+    }
+
+    /// Removes the last element, lends it to `f` and drops it afterwards, without allocating.
+    ///
+    /// Returns `None` if the [`FuseBox`] is empty.
+    /// The element is dropped even if `f` panics.
+    pub fn pop_with<F, R>(&mut self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Dyn) -> R,
+    {
+        struct DropGuard<Dyn: ?Sized>(*mut Dyn);
+
+        impl<Dyn: ?Sized> Drop for DropGuard<Dyn> {
+            fn drop(&mut self) {
+                // Safety: the value is no longer owned by the FuseBox
+                unsafe { drop_in_place(self.0) }
+            }
+        }
+
+        let n = self.len().checked_sub(1)?;
+        self.invalidate_handles();
+        let end = self.end_of(n);
+        // Safety: n is in-bounds, the bytes stay untouched until the guard is dropped
+        let guard = DropGuard(unsafe { self.get_raw(n) });
+        self.headers.pop();
+        self.bytes.len_bytes = end;
+        Some(f(unsafe { &mut *guard.0 }))
+    }
+
+    #[cfg(feature = "nightly")]
+    /// Drops the element at `n` and puts `v` at the same index.
+    ///
+    /// `v` is written in place if it fits in the old slot, otherwise later elements are moved.
+    /// Returns `true` in that case, as pointers to other elements are then invalidated.
+    ///
+    /// # Panics
+    /// Panics if `n` is out of bounds.
+    pub fn replace<T>(&mut self, n: usize, v: T) -> bool
     where
         T: 'static,
         T: Unsize<Dyn>,
         Dyn: 'static,
     {
-        unsafe { self.push_unsafe(v) }
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.replace_coerce(n, v, |p| p) }
+    }
+
+    /// Drops the element at `n` and puts `v` at the same index, using `coerce` to unsize it.
+    ///
+    /// See [`FuseBox::replace`] and [`FuseBox::push_coerce`].
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn replace_coerce<T>(
+        &mut self,
+        n: usize,
+        v: T,
+        coerce: fn(*mut T) -> *mut Dyn,
+    ) -> bool
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        assert!(n < self.len(), "index out of bounds");
+        self.invalidate_handles();
+        let meta = unsafe { meta::from_coerce(coerce) };
+        // If the old value panics while dropping, `v` is still owned here and dropped too
+        unsafe { self.drop_raw(n) };
+        let v = ManuallyDrop::new(v);
+        unsafe { self.replace_raw(n, (&*v as *const T).cast(), Layout::new::<T>(), meta) }
+    }
+
+    /// Drops the value at `n`, leaving a header that points at dead bytes.
+    ///
+    /// If the destructor panics the header is removed instead.
+    ///
+    /// # Safety
+    /// The header at `n` must be overwritten with [`FuseBox::replace_raw`] right after.
+    unsafe fn drop_raw(&mut self, n: usize) {
+        struct RemoveGuard<'f, Dyn: ?Sized>(&'f mut FuseBox<Dyn>, usize);
+
+        impl<Dyn: ?Sized> Drop for RemoveGuard<'_, Dyn> {
+            fn drop(&mut self) {
+                // Safety: the value has been dropped as far as possible
+                unsafe { self.0.remove_raw(self.1) }
+            }
+        }
+
+        let guard = RemoveGuard(self, n);
+        unsafe { drop_in_place(guard.0.get_raw(n)) };
+        mem::forget(guard);
+    }
+
+    /// Bitwise moves a value described by `layout` and `meta` from `src` into the dead slot `n`.
+    ///
+    /// Returns `true` if other elements had to be moved.
+    ///
+    /// # Safety
+    /// The value at `n` must have been moved out or dropped already.
+    /// `src` must point to a valid value of that layout and metadata,
+    /// which the caller must not use or drop afterwards.
+    unsafe fn replace_raw(
+        &mut self,
+        n: usize,
+        src: *const u8,
+        layout: Layout,
+        meta: Metadata<Dyn>,
+    ) -> bool {
+        let mut offset = round_up(self.end_of(n), layout.align());
+        let limit = match self.headers.get(n + 1) {
+            Some(next) => next.offset,
+            None => self.bytes.cap_bytes,
+        };
+        let in_place = (layout.size() == 0 && layout.align() == 1)
+            || (offset + layout.size() <= limit && layout.align() <= self.bytes.max_align);
+
+        if !in_place {
+            self.bytes.grow_exact(self.bytes.cap_bytes, layout.align());
+            offset = round_up(self.end_of(n), layout.align());
+            self.shift_up(n + 1, offset + layout.size());
+        } else if n + 1 == self.len() {
+            self.bytes.len_bytes = offset + layout.size();
+        }
+
+        // Safety: offset + size is in-bounds and does not overlap other values
+        unsafe {
+            ptr::copy_nonoverlapping(src, self.bytes.inner.as_ptr().add(offset), layout.size());
+        }
+        self.headers[n] = Header { offset, meta };
+        !in_place
+    }
+
+    /// Keeps only the elements for which `f` returns `true`, dropping the others.
+    ///
+    /// Kept elements are slid down over the gaps in a single pass.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Dyn) -> bool,
+    {
+        self.retain_mut(|v| f(v));
+    }
+
+    /// Keeps only the elements for which `f` returns `true`, dropping the others.
+    ///
+    /// If `f` or a destructor panics, the elements not visited yet are kept.
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Dyn) -> bool,
+    {
+        let mut compactor = Compactor::new(self, 0);
+        while let Some(v) = compactor.next_ptr() {
+            // Safety: the value stays valid until it's kept or removed
+            if f(unsafe { &mut *v }) {
+                compactor.keep_next();
+            } else {
+                unsafe { drop_in_place(compactor.remove_next()) };
+            }
+        }
+    }
+
+    /// Removes the elements in `range`, yielding each of them in its own [`Box`].
+    ///
+    /// The gap is closed when the iterator is dropped,
+    /// elements of the range that were not consumed are dropped too.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        let Range { start, end } = to_range(range, self.len());
+        Drain::new(self, start, end)
+    }
+
+    /// Removes the elements for which `pred` returns `true`, yielding each of them in its own [`Box`].
+    ///
+    /// Elements are visited lazily, those not visited when the iterator is dropped are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, Dyn, F>
+    where
+        F: FnMut(&mut Dyn) -> bool,
+    {
+        ExtractIf::new(self, pred)
+    }
+
+    /// Moves all elements of `other` to the end of `self`, leaving `other` empty.
+    ///
+    /// Room for all of them is reserved at once, and each is re-aligned after the last element.
+    pub fn append(&mut self, other: &mut Self) {
+        self.move_from(other, 0);
+    }
+
+    /// Splits the [`FuseBox`] in two, returning the elements from `at` on.
+    ///
+    /// # Panics
+    /// Panics if `at` is greater than the length.
+    #[must_use]
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len(), "at out of bounds");
+        let mut tail = Self::new();
+        tail.move_from(self, at);
+        tail
+    }
+
+    /// Moves the element at `n` to the end of `other` without boxing it,
+    /// shifting later elements down.
+    ///
+    /// # Panics
+    /// Panics if `n` is out of bounds.
+    pub fn transfer(&mut self, n: usize, other: &mut Self) {
+        assert!(n < self.len(), "index out of bounds");
+        let Header { offset, meta } = self.headers[n];
+        let layout = self.layout_of(n);
+        // Safety: the value is moved to other before its header is removed
+        unsafe {
+            other.push_raw(self.bytes.inner.as_ptr().add(offset), layout, meta);
+            self.remove_raw(n);
+        }
+    }
+
+    /// Moves the elements of `other` from `start` on to the end of `self`.
+    fn move_from(&mut self, other: &mut Self, start: usize) {
+        let mut len_bytes = self.bytes.len_bytes;
+        let mut max_align = 1;
+        for n in start..other.len() {
+            let layout = other.layout_of(n);
+            len_bytes = round_up(len_bytes, layout.align()) + layout.size();
+            max_align = max_align.max(layout.align());
+        }
+        self.headers.reserve(other.len() - start);
+        self.bytes.reserve_bytes(len_bytes, max_align);
+
+        for n in start..other.len() {
+            let Header { offset, meta } = other.headers[n];
+            // Safety: other forgets the value right after
+            unsafe {
+                self.push_raw(
+                    other.bytes.inner.as_ptr().add(offset),
+                    other.layout_of(n),
+                    meta,
+                );
+            }
+        }
+        other.invalidate_handles();
+        other.bytes.len_bytes = other.end_of(start);
+        other.headers.truncate(start);
     }
 
     #[inline]
     pub(crate) unsafe fn get_raw(&self, n: usize) -> *mut Dyn {
         let Header { offset, meta } = self.headers[n];
         unsafe {
-            let ptr = self.inner.as_ptr().add(offset).cast();
-            ptr::from_raw_parts_mut::<Dyn>(ptr, meta)
+            let ptr = self.bytes.inner.as_ptr().add(offset);
+            meta::from_raw_parts_mut(ptr, meta)
         }
     }
 
@@ -243,8 +643,371 @@ where
     pub fn iter_mut(&'_ mut self) -> IterMut<'_, Dyn> {
         IterMut::new(self)
     }
+
+    #[must_use]
+    /// Returns a view of all elements.
+    pub fn as_slice(&self) -> FuseSlice<'_, Dyn> {
+        // Safety: headers and inner are borrowed from self
+        unsafe { FuseSlice::from_parts(&self.headers, self.bytes.inner) }
+    }
+
+    #[must_use]
+    /// Returns a mutable view of all elements.
+    pub fn as_mut_slice(&mut self) -> FuseSliceMut<'_, Dyn> {
+        // Safety: self is borrowed mutably
+        unsafe { FuseSliceMut::from_parts(&self.headers, self.bytes.inner) }
+    }
+
+    #[must_use]
+    /// Returns a view of the elements in `range`.
+    ///
+    /// This stands in for `&fb[range]`, as [`Index`] can't return a view type.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn slice<R>(&self, range: R) -> FuseSlice<'_, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        self.as_slice().slice(range)
+    }
+
+    #[must_use]
+    /// Returns a mutable view of the elements in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn slice_mut<R>(&mut self, range: R) -> FuseSliceMut<'_, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        let Range { start, end } = to_range(range, self.len());
+        // Safety: self is borrowed mutably
+        unsafe { FuseSliceMut::from_parts(&self.headers[start..end], self.bytes.inner) }
+    }
+
+    #[must_use]
+    /// Returns an iterator over all overlapping views of `size` elements.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn windows(&self, size: usize) -> Windows<'_, Dyn> {
+        self.as_slice().windows(size)
+    }
+
+    #[must_use]
+    /// Returns an iterator over views of `size` elements, the last one may be shorter.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn chunks(&self, size: usize) -> Chunks<'_, Dyn> {
+        self.as_slice().chunks(size)
+    }
+
+    #[must_use]
+    /// Returns an iterator over mutable views of `size` elements, the last one may be shorter.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn chunks_mut(&mut self, size: usize) -> ChunksMut<'_, Dyn> {
+        self.as_mut_slice().into_chunks_mut(size)
+    }
+
+    #[must_use]
+    /// Returns a lending iterator over `(&mut Dyn, &mut Dyn)` for every pair of neighbors,
+    /// e.g. to feed each node's output into the next one.
+    pub fn pairs_mut(&mut self) -> PairsMut<'_, Dyn> {
+        self.as_mut_slice().into_pairs_mut()
+    }
+
+    #[must_use]
+    /// Divides the [`FuseBox`] into two mutable views at `mid`.
+    ///
+    /// # Panics
+    /// Panics if `mid` is greater than the length.
+    pub fn split_at_mut(&mut self, mid: usize) -> (FuseSliceMut<'_, Dyn>, FuseSliceMut<'_, Dyn>) {
+        self.as_mut_slice().into_split_at(mid)
+    }
+
+    /// Returns the first element and a view of the rest, or `None` if empty.
+    pub fn split_first_mut(&mut self) -> Option<(&mut Dyn, FuseSliceMut<'_, Dyn>)> {
+        self.as_mut_slice().into_split_first()
+    }
+
+    /// Returns the last element and a view of the rest, or `None` if empty.
+    pub fn split_last_mut(&mut self) -> Option<(&mut Dyn, FuseSliceMut<'_, Dyn>)> {
+        self.as_mut_slice().into_split_last()
+    }
+
+    /// Returns mutable references to several elements at once.
+    ///
+    /// Fails if any index is out of bounds or appears twice.
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Result<[&mut Dyn; N], GetDisjointMutError> {
+        self.as_mut_slice().into_disjoint_mut(indices)
+    }
+
+    #[inline]
+    pub(crate) fn layout_of(&self, n: usize) -> Layout {
+        unsafe { Layout::for_value(&*self.get_raw(n)) }
+    }
+
+    /// Physically regroups elements so that all values sharing the same
+    /// metadata (i.e. the same vtable for `dyn Trait`) are stored next to each other.
+    ///
+    /// Groups are ordered by first appearance, and the sort is stable within each group.
+    ///
+    /// Grouping is best-effort: Rust does not promise a single vtable per type,
+    /// so values of one type may still end up in separate groups.
+    /// Without the `nightly` feature, metadata is captured by the `coerce` function,
+    /// which makes this the rule for values pushed from different
+    /// [`fuse_push!`](crate::fuse_push) call sites.
+    pub fn sort_by_type(&mut self) {
+        let mut ranks = BTreeMap::new();
+        for Header { meta, .. } in &self.headers {
+            let next = ranks.len();
+            ranks.entry(*meta).or_insert(next);
+        }
+        if ranks.len() <= 1 {
+            return;
+        }
+
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        order.sort_by_key(|&n| ranks[&self.headers[n].meta]);
+        self.permute(0, &order);
+    }
+
+    /// Physically reorders elements with a comparator function.
+    ///
+    /// The sort is stable and `inner` is rebuilt in the new order, re-aligning every element.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&Dyn, &Dyn) -> Ordering,
+    {
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        // Safety: indices are in-bounds and only shared references are handed out
+        order.sort_by(|&a, &b| unsafe { compare(&*self.get_raw(a), &*self.get_raw(b)) });
+        self.permute(0, &order);
+    }
+
+    /// Physically reorders elements with a key extraction function.
+    ///
+    /// See [`FuseBox::sort_by`].
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Dyn) -> K,
+        K: Ord,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Swaps two elements, moving the bytes between them as needed.
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` are out of bounds.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        assert!(i < self.len() && j < self.len(), "index out of bounds");
+        let (lo, hi) = match i.cmp(&j) {
+            Ordering::Less => (i, j),
+            Ordering::Greater => (j, i),
+            Ordering::Equal => return,
+        };
+        let order: Vec<usize> = core::iter::once(hi)
+            .chain(lo + 1..hi)
+            .chain(core::iter::once(lo))
+            .collect();
+        self.permute(lo, &order);
+    }
+
+    /// Rotates the elements so that the element at `mid` becomes the first one.
+    ///
+    /// # Panics
+    /// Panics if `mid` is greater than the length.
+    pub fn rotate_left(&mut self, mid: usize) {
+        assert!(mid <= self.len(), "mid out of bounds");
+        let order: Vec<usize> = (mid..self.len()).chain(0..mid).collect();
+        self.permute(0, &order);
+    }
+
+    /// Rotates the elements so that the last `k` elements come first.
+    ///
+    /// # Panics
+    /// Panics if `k` is greater than the length.
+    pub fn rotate_right(&mut self, k: usize) {
+        assert!(k <= self.len(), "k out of bounds");
+        self.rotate_left(self.len() - k);
+    }
+
+    /// Calls `f` once for every run of consecutive elements sharing the same metadata.
+    ///
+    /// After [`FuseBox::sort_by_type`] there is exactly one run per metadata,
+    /// so every call dispatches to a single implementation,
+    /// but values of one type may span several runs.
+    pub fn for_each_group<F>(&self, mut f: F)
+    where
+        F: FnMut(Metadata<Dyn>, Iter<'_, Dyn>),
+    {
+        let mut rest = &self.headers[..];
+        while let Some(&Header { meta, .. }) = rest.first() {
+            let run = rest.iter().take_while(|h| h.meta == meta).count();
+            let (group, tail) = rest.split_at(run);
+            // Safety: headers are borrowed from self
+            f(meta, unsafe { Iter::from_parts(group, self.bytes.inner) });
+            rest = tail;
+        }
+    }
+
+    /// Mutable version of [`FuseBox::for_each_group`].
+    pub fn for_each_group_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Metadata<Dyn>, IterMut<'_, Dyn>),
+    {
+        let mut rest = &self.headers[..];
+        while let Some(&Header { meta, .. }) = rest.first() {
+            let run = rest.iter().take_while(|h| h.meta == meta).count();
+            let (group, tail) = rest.split_at(run);
+            // Safety: self is borrowed mutably and groups are disjoint
+            f(meta, unsafe {
+                IterMut::from_parts(group, self.bytes.inner)
+            });
+            rest = tail;
+        }
+    }
+
+    /// Makes every [`Handle`](handle::Handle) issued so far invalid.
+    ///
+    /// Must be called before elements are removed, moved to another index or replaced.
+    #[inline]
+    fn invalidate_handles(&mut self) {
+        self.stamp = 0;
+    }
+
+    /// Returns the end of the bytes used by the elements before `n`.
+    #[inline]
+    fn end_of(&self, n: usize) -> usize {
+        match n.checked_sub(1) {
+            None => 0,
+            Some(prev) => self.headers[prev].offset + self.layout_of(prev).size(),
+        }
+    }
+
+    /// Moves the elements from `from` on down so that they start right after `end`,
+    /// re-aligning each of them.
+    ///
+    /// Offsets only decrease, so moving front to back never overwrites a value not moved yet.
+    fn shift_down(&mut self, from: usize, mut end: usize) {
+        for n in from..self.len() {
+            let layout = self.layout_of(n);
+            let offset = round_up(end, layout.align());
+            let old = self.headers[n].offset;
+            debug_assert!(offset <= old);
+            if offset != old {
+                // Safety: both ranges are in-bounds
+                unsafe {
+                    let base = self.bytes.inner.as_ptr();
+                    ptr::copy(base.add(old), base.add(offset), layout.size());
+                }
+                self.headers[n].offset = offset;
+            }
+            end = offset + layout.size();
+        }
+        self.bytes.len_bytes = end;
+    }
+
+    /// Moves the elements from `from` on up so that they start at or after `end`,
+    /// growing `inner` if needed. Gaps that are still large enough are kept.
+    ///
+    /// Offsets only increase, so moving back to front never overwrites a value not moved yet.
+    fn shift_up(&mut self, from: usize, mut end: usize) {
+        let mut offsets = Vec::with_capacity(self.len() - from);
+        for n in from..self.len() {
+            let layout = self.layout_of(n);
+            let offset = round_up(end, layout.align()).max(self.headers[n].offset);
+            offsets.push(offset);
+            end = offset + layout.size();
+        }
+
+        self.bytes.reserve_bytes(end, 1);
+        for (n, offset) in (from..self.len()).zip(offsets).rev() {
+            let old = self.headers[n].offset;
+            if offset != old {
+                // Safety: both ranges are in-bounds
+                unsafe {
+                    let base = self.bytes.inner.as_ptr();
+                    ptr::copy(base.add(old), base.add(offset), self.layout_of(n).size());
+                }
+                self.headers[n].offset = offset;
+            }
+        }
+        self.bytes.len_bytes = end;
+    }
+
+    /// Removes the header at `n` without dropping the value and closes the gap.
+    ///
+    /// # Safety
+    /// The value at `n` must have been moved out or dropped already.
+    unsafe fn remove_raw(&mut self, n: usize) {
+        self.invalidate_handles();
+        let end = self.end_of(n);
+        self.headers.remove(n);
+        self.shift_down(n, end);
+    }
+
+    /// Moves the element previously at `order[i]` to index `start + i`,
+    /// where `order` is a permutation of `start..start + order.len()`.
+    ///
+    /// The affected bytes go through a scratch buffer, later elements are shifted as needed.
+    fn permute(&mut self, start: usize, order: &[usize]) {
+        let end = start + order.len();
+        debug_assert!(order.iter().all(|n| (start..end).contains(n)));
+        if (start..).zip(order).all(|(n, &m)| n == m) {
+            return;
+        }
+        self.invalidate_handles();
+
+        let base = self.end_of(start);
+        let old_end = self.end_of(end);
+        let mut scratch = Vec::<MaybeUninit<u8>>::with_capacity(old_end - base);
+        // Safety: base..old_end is in-bounds
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.bytes.inner.as_ptr().add(base),
+                scratch.as_mut_ptr().cast(),
+                old_end - base,
+            );
+        }
+
+        let mut moved = Vec::with_capacity(order.len());
+        let mut new_end = base;
+        for &n in order {
+            let layout = self.layout_of(n);
+            let offset = round_up(new_end, layout.align());
+            moved.push((self.headers[n], offset, layout.size()));
+            new_end = offset + layout.size();
+        }
+
+        if new_end > old_end {
+            self.shift_up(end, new_end);
+        } else {
+            self.shift_down(end, new_end);
+        }
+        for (n, (Header { offset: old, meta }, offset, size)) in (start..).zip(moved) {
+            // Safety: new offsets are in-bounds and don't overlap the shifted tail
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    scratch.as_ptr().add(old - base).cast(),
+                    self.bytes.inner.as_ptr().add(offset),
+                    size,
+                );
+            }
+            self.headers[n] = Header { offset, meta };
+        }
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl<Dyn> Index<usize> for FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -258,6 +1021,7 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<Dyn> IndexMut<usize> for FuseBox<Dyn>
 where
     Dyn: ?Sized,
@@ -269,6 +1033,175 @@ where
     }
 }
 
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<Dyn, T> Index<Handle<T>> for FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Output = T;
+
+    #[inline]
+    fn index(&self, handle: Handle<T>) -> &Self::Output {
+        unsafe { &*self.handle_ptr(handle) }
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<Dyn, T> IndexMut<Handle<T>> for FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, handle: Handle<T>) -> &mut Self::Output {
+        unsafe { &mut *self.handle_ptr(handle) }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> IntoIterator for FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = Box<Dyn>;
+    type IntoIter = IntoIter<Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'f, Dyn> IntoIterator for &'f FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = &'f Dyn;
+    type IntoIter = Iter<'f, Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'f, Dyn> IntoIterator for &'f mut FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = &'f mut Dyn;
+    type IntoIter = IterMut<'f, Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+impl<Dyn, T> Extend<T> for FuseBox<Dyn>
+where
+    T: 'static,
+    T: Unsize<Dyn>,
+    Dyn: ?Sized + 'static,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for v in iter {
+            self.push(v)
+        }
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+impl<Dyn> From<Vec<Box<Dyn>>> for FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// Moves every boxed value into a [`FuseBox`], allocating exactly once.
+    fn from(boxes: Vec<Box<Dyn>>) -> Self {
+        let mut len_bytes = 0;
+        let mut max_align = 1;
+        for b in &boxes {
+            let layout = Layout::for_value(&**b);
+            len_bytes = round_up(len_bytes, layout.align()) + layout.size();
+            max_align = max_align.max(layout.align());
+        }
+
+        let mut fused = Self::new();
+        fused.headers.reserve_exact(boxes.len());
+        fused.bytes.grow_exact(len_bytes, max_align);
+        fused.extend_boxed(boxes);
+        fused
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+impl<Dyn, T> FromIterator<T> for FuseBox<Dyn>
+where
+    T: 'static,
+    T: Unsize<Dyn>,
+    Dyn: ?Sized + 'static,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut fused = Self::new();
+        fused.extend(iter);
+        fused
+    }
+}
+
+/// Bitwise moves a value described by `layout` and `meta` from `src` into a fresh [`Box`].
+///
+/// # Safety
+/// `src` must point to a valid value of that layout and metadata,
+/// which the caller must not use or drop afterwards.
+#[cfg(feature = "alloc")]
+pub(crate) unsafe fn move_to_box<Dyn>(
+    src: *const u8,
+    layout: Layout,
+    meta: Metadata<Dyn>,
+) -> Box<Dyn>
+where
+    Dyn: ?Sized,
+{
+    let dst = if layout.size() == 0 {
+        ptr::without_provenance_mut(layout.align())
+    } else {
+        let dst = unsafe { alloc(layout) };
+        if dst.is_null() {
+            handle_alloc_error(layout)
+        }
+        dst
+    };
+    unsafe {
+        ptr::copy_nonoverlapping(src, dst, layout.size());
+        Box::from_raw(meta::from_raw_parts_mut(dst, meta))
+    }
+}
+
+/// Resolves `range` against a length, panicking like slice indexing does.
+fn to_range<R>(range: R, len: usize) -> Range<usize>
+where
+    R: RangeBounds<usize>,
+{
+    let start = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n.checked_add(1).expect("range start overflowed usize"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n.checked_add(1).expect("range end overflowed usize"),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts at {start} but ends at {end}");
+    assert!(end <= len, "range end {end} out of bounds for length {len}");
+    start..end
+}
+
 fn round_up(n: usize, m: usize) -> usize {
     if m == 0 {
         n
@@ -0,0 +1,236 @@
+#[cfg(feature = "nightly")]
+use core::marker::Unsize;
+use core::{
+    alloc::Layout,
+    mem::{align_of, MaybeUninit},
+    ops::{Index, IndexMut},
+    ptr::{drop_in_place, NonNull},
+    slice,
+};
+
+use super::{
+    iter::{Iter, IterMut},
+    meta, round_up, Header,
+};
+
+/// Byte storage aligned for any value with alignment up to 16.
+#[repr(C, align(16))]
+struct Bytes<const N: usize>([MaybeUninit<u8>; N]);
+
+/// Fixed-capacity [`FuseBox`](crate::FuseBox) that never allocates
+///
+/// Holds at most `N` bytes of values and `H` elements.
+/// Values aligned to more than 16 bytes are always rejected.
+///
+/// `Dyn` shall be `dyn Trait`
+///
+/// ```
+/// # use std::fmt::Debug;
+/// # use fusebox::ArrayFuseBox;
+/// static EMPTY: ArrayFuseBox<dyn Debug + Sync, 64, 4> = ArrayFuseBox::new();
+/// assert!(EMPTY.is_empty());
+/// ```
+pub struct ArrayFuseBox<Dyn, const N: usize, const H: usize = 8>
+where
+    Dyn: ?Sized,
+{
+    headers: [MaybeUninit<Header<Dyn>>; H],
+    len: usize,
+    len_bytes: usize,
+    bytes: Bytes<N>,
+}
+
+impl<Dyn, const N: usize, const H: usize> Default for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Dyn, const N: usize, const H: usize> Drop for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        // Safety: headers below len describe valid values
+        unsafe {
+            for val in self.iter_mut() {
+                drop_in_place(val);
+            }
+        }
+    }
+}
+
+unsafe impl<Dyn, const N: usize, const H: usize> Send for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+    Dyn: Send,
+{
+}
+
+unsafe impl<Dyn, const N: usize, const H: usize> Sync for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+impl<Dyn, const N: usize, const H: usize> ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    #[must_use]
+    /// Creates a new empty [`ArrayFuseBox<Dyn, N, H>`].
+    pub const fn new() -> Self {
+        Self {
+            headers: [const { MaybeUninit::uninit() }; H],
+            len: 0,
+            len_bytes: 0,
+            bytes: Bytes([MaybeUninit::uninit(); N]),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the length of this [`ArrayFuseBox<Dyn, N, H>`] in items.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub(crate) fn headers(&self) -> &[Header<Dyn>] {
+        unsafe { slice::from_raw_parts(self.headers.as_ptr().cast(), self.len) }
+    }
+
+    #[inline]
+    pub(crate) fn base(&self) -> NonNull<u8> {
+        NonNull::from(&self.bytes).cast()
+    }
+
+    #[inline]
+    pub(crate) fn base_mut(&mut self) -> NonNull<u8> {
+        NonNull::from(&mut self.bytes).cast()
+    }
+
+    #[cfg(feature = "nightly")]
+    #[inline]
+    /// Appends an element to the vector, or gives it back if there is no room left.
+    pub fn push<T>(&mut self, v: T) -> Result<(), T>
+    where
+        T: 'static,
+        T: Unsize<Dyn>,
+        Dyn: 'static,
+    {
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce(v, |p| p) }
+    }
+
+    #[inline]
+    /// Appends an element to the vector using `coerce` to unsize it,
+    /// or gives it back if there is no room left.
+    ///
+    /// See [`FuseBox::push_coerce`](crate::FuseBox::push_coerce).
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce<T>(&mut self, v: T, coerce: fn(*mut T) -> *mut Dyn) -> Result<(), T>
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        let layout = Layout::new::<T>();
+        if self.len == H || layout.align() > align_of::<Bytes<N>>() {
+            return Err(v);
+        }
+        let offset = round_up(self.len_bytes, layout.align());
+        if offset + layout.size() > N {
+            return Err(v);
+        }
+
+        let meta = unsafe { meta::from_coerce(coerce) };
+        // Safety: offset + size is in-bounds and aligned for T
+        unsafe { self.base_mut().as_ptr().add(offset).cast::<T>().write(v) }
+        self.headers[self.len].write(Header { offset, meta });
+        self.len += 1;
+        self.len_bytes = offset + layout.size();
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn get_raw(&self, n: usize) -> *mut Dyn {
+        let Header { offset, meta } = self.headers()[n];
+        unsafe { meta::from_raw_parts_mut(self.base().as_ptr().add(offset), meta) }
+    }
+
+    #[inline]
+    unsafe fn get_raw_mut(&mut self, n: usize) -> *mut Dyn {
+        let Header { offset, meta } = self.headers()[n];
+        unsafe { meta::from_raw_parts_mut(self.base_mut().as_ptr().add(offset), meta) }
+    }
+
+    #[inline]
+    /// Retrieves `&mut Dyn` from [`ArrayFuseBox`].
+    pub fn get_mut(&mut self, n: usize) -> Option<&mut Dyn> {
+        if self.len() <= n {
+            return None;
+        }
+        unsafe { Some(&mut *self.get_raw_mut(n)) }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Retrieves `&Dyn` from [`ArrayFuseBox`].
+    pub fn get(&self, n: usize) -> Option<&Dyn> {
+        if self.len() <= n {
+            return None;
+        }
+        unsafe { Some(&*self.get_raw(n)) }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&Dyn` stored in this [`ArrayFuseBox`]
+    pub fn iter(&'_ self) -> Iter<'_, Dyn> {
+        // Safety: headers and bytes are borrowed from self
+        unsafe { Iter::from_parts(self.headers(), self.base()) }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&mut Dyn` stored in this [`ArrayFuseBox`].
+    pub fn iter_mut(&'_ mut self) -> IterMut<'_, Dyn> {
+        let base = self.base_mut();
+        // Safety: self is borrowed mutably
+        unsafe { IterMut::from_parts(self.headers(), base) }
+    }
+}
+
+impl<Dyn, const N: usize, const H: usize> Index<usize> for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len());
+        unsafe { &*self.get_raw(index) }
+    }
+}
+
+impl<Dyn, const N: usize, const H: usize> IndexMut<usize> for ArrayFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        assert!(index < self.len());
+        unsafe { &mut *self.get_raw_mut(index) }
+    }
+}
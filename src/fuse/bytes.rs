@@ -0,0 +1,103 @@
+use alloc::alloc::{alloc, dealloc};
+use core::{
+    alloc::Layout,
+    ptr::{self, NonNull},
+};
+
+/// Growable byte buffer backing [`FuseBox`](super::FuseBox) and [`FuseSlab`](super::slab::FuseSlab)
+///
+/// Only tracks the bytes, owners are responsible for the values stored in them.
+pub(crate) struct RawBytes {
+    pub(crate) inner: NonNull<u8>,
+    pub(crate) max_align: usize,
+    /// Number of bytes in use, the ones copied when growing.
+    pub(crate) len_bytes: usize,
+    pub(crate) cap_bytes: usize,
+}
+
+impl Drop for RawBytes {
+    fn drop(&mut self) {
+        if self.cap_bytes != 0 {
+            // Safety: inner was allocated with this layout
+            unsafe {
+                dealloc(
+                    self.inner.as_ptr(),
+                    Layout::from_size_align_unchecked(self.cap_bytes, self.max_align),
+                );
+            }
+        }
+    }
+}
+
+impl RawBytes {
+    pub(crate) const fn new() -> Self {
+        Self {
+            inner: NonNull::dangling(),
+            max_align: 1,
+            len_bytes: 0,
+            cap_bytes: 0,
+        }
+    }
+
+    /// Grows to fit at least `min_layout` past the current capacity.
+    #[inline]
+    pub(crate) fn realloc(&mut self, min_layout: Layout) {
+        let size = if self.cap_bytes == 0 {
+            min_layout.size()
+        } else {
+            self.cap_bytes
+                .checked_mul(2)
+                .and_then(|s| s.checked_add(min_layout.size()))
+                .expect("New capacity overflowed usize")
+        };
+        self.grow_exact(size, min_layout.align());
+    }
+
+    /// Moves `inner` to an allocation of at least `cap_bytes` bytes, aligned to at least `align`.
+    pub(crate) fn grow_exact(&mut self, cap_bytes: usize, align: usize) {
+        let max_align = self.max_align.max(align);
+        if cap_bytes <= self.cap_bytes && max_align == self.max_align {
+            return;
+        }
+        let layout = Layout::from_size_align(cap_bytes.max(self.cap_bytes), max_align)
+            .expect("New capacity overflowed isize")
+            .pad_to_align();
+
+        if layout.size() == 0 {
+            // Only zero-sized values so far, they just need an aligned base
+            self.max_align = max_align;
+            self.inner = unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(max_align)) };
+            return;
+        }
+
+        unsafe {
+            let new = alloc(layout);
+            if new.is_null() {
+                panic!(
+                    "Failed to allocate memory for {}",
+                    core::any::type_name::<Self>()
+                )
+            }
+            if self.cap_bytes != 0 {
+                ptr::copy_nonoverlapping(self.inner.as_ptr(), new, self.len_bytes);
+                dealloc(
+                    self.inner.as_ptr(),
+                    Layout::from_size_align_unchecked(self.cap_bytes, self.max_align),
+                );
+            }
+            self.inner = NonNull::new_unchecked(new);
+        }
+        self.max_align = max_align;
+        self.cap_bytes = layout.size();
+    }
+
+    /// Makes room for `len_bytes` bytes aligned to `align`, growing at least twofold if needed.
+    pub(crate) fn reserve_bytes(&mut self, len_bytes: usize, align: usize) {
+        let cap_bytes = if len_bytes > self.cap_bytes {
+            len_bytes.max(self.cap_bytes.saturating_mul(2))
+        } else {
+            self.cap_bytes
+        };
+        self.grow_exact(cap_bytes, align);
+    }
+}
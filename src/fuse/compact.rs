@@ -0,0 +1,125 @@
+use alloc::boxed::Box;
+use core::{alloc::Layout, ptr};
+
+use super::{meta, move_to_box, round_up, FuseBox, Header};
+
+/// Removes elements from a [`FuseBox`] in one front-to-back pass,
+/// sliding kept elements down over the gaps.
+///
+/// Headers from `start` on are hidden from the [`FuseBox`] while this is alive,
+/// so leaking it leaks those values instead of dropping them twice.
+/// On drop, every element not processed yet is kept.
+pub(crate) struct Compactor<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    fused: &'f mut FuseBox<Dyn>,
+    len: usize,
+    processed: usize,
+    kept: usize,
+    end: usize,
+}
+
+impl<'f, Dyn> Compactor<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// Starts at element `start`, leaving earlier elements untouched.
+    pub(crate) fn new(fused: &'f mut FuseBox<Dyn>, start: usize) -> Self {
+        let len = fused.len();
+        fused.invalidate_handles();
+        let end = fused.end_of(start);
+        unsafe { fused.headers.set_len(start) };
+        fused.bytes.len_bytes = end;
+        Self {
+            fused,
+            len,
+            processed: start,
+            kept: start,
+            end,
+        }
+    }
+
+    /// Number of elements processed so far, including untouched ones.
+    #[inline]
+    pub(crate) fn processed(&self) -> usize {
+        self.processed
+    }
+
+    #[inline]
+    fn next_header(&self) -> Option<Header<Dyn>> {
+        if self.processed == self.len {
+            return None;
+        }
+        // Safety: headers below len are still initialized
+        Some(unsafe { *self.fused.headers.as_ptr().add(self.processed) })
+    }
+
+    /// Returns the next element, which stays valid until it's kept or removed.
+    #[inline]
+    pub(crate) fn next_ptr(&self) -> Option<*mut Dyn> {
+        let Header { offset, meta } = self.next_header()?;
+        Some(meta::from_raw_parts_mut(
+            unsafe { self.fused.bytes.inner.as_ptr().add(offset) },
+            meta,
+        ))
+    }
+
+    /// Keeps the next element, moving it down right after the previously kept one.
+    pub(crate) fn keep_next(&mut self) {
+        let Some(Header { offset, meta }) = self.next_header() else {
+            return;
+        };
+        let base = self.fused.bytes.inner.as_ptr();
+        // Safety: offsets only decrease, so earlier elements never overlap later ones
+        unsafe {
+            let layout =
+                Layout::for_value(&*meta::from_raw_parts_mut::<Dyn>(base.add(offset), meta));
+            let new = round_up(self.end, layout.align());
+            if new != offset {
+                ptr::copy(base.add(offset), base.add(new), layout.size());
+            }
+            *self.fused.headers.as_mut_ptr().add(self.kept) = Header { offset: new, meta };
+            self.end = new + layout.size();
+        }
+        self.kept += 1;
+        self.processed += 1;
+    }
+
+    /// Skips the next element, handing its ownership to the caller.
+    ///
+    /// # Safety
+    /// There must be a next element, which the caller must move out or drop.
+    #[inline]
+    pub(crate) unsafe fn remove_next(&mut self) -> *mut Dyn {
+        let ptr = unsafe { self.next_ptr().unwrap_unchecked() };
+        self.processed += 1;
+        ptr
+    }
+
+    /// Skips the next element, moving it into its own [`Box`].
+    ///
+    /// # Safety
+    /// There must be a next element.
+    #[inline]
+    pub(crate) unsafe fn remove_next_boxed(&mut self) -> Box<Dyn> {
+        let Header { offset, meta } = unsafe { self.next_header().unwrap_unchecked() };
+        let src = unsafe { self.fused.bytes.inner.as_ptr().add(offset) };
+        let layout = unsafe { Layout::for_value(&*meta::from_raw_parts_mut::<Dyn>(src, meta)) };
+        self.processed += 1;
+        unsafe { move_to_box(src, layout, meta) }
+    }
+}
+
+impl<Dyn> Drop for Compactor<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        while self.processed < self.len {
+            self.keep_next();
+        }
+        unsafe { self.fused.headers.set_len(self.kept) };
+        self.fused.bytes.len_bytes = self.end;
+    }
+}
@@ -0,0 +1,89 @@
+//! Typed handles to elements of a [`FuseBox`](crate::FuseBox)
+
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Typed index of a value pushed with [`FuseBox::push_handle`](crate::FuseBox::push_handle)
+///
+/// Indexing a [`FuseBox`](crate::FuseBox) with it gives `&T` without downcasting.
+/// Handles only stay valid while elements are appended:
+/// anything that removes, reorders or replaces elements invalidates all of them,
+/// and using an invalid handle panics.
+///
+/// ```
+/// # use std::fmt::Debug;
+/// # use fusebox::FuseBox;
+/// # #[cfg(feature = "nightly")] {
+/// let mut fb = FuseBox::<dyn Debug>::new();
+/// let freq = fb.push_handle(440.0f32);
+/// fb.push("other");
+/// fb[freq] *= 2.0;
+/// assert_eq!(fb[freq], 880.0);
+/// assert_eq!(format!("{:?}", fb.dyn_ref(freq)), "880.0");
+/// # }
+/// ```
+pub struct Handle<T> {
+    pub(crate) index: usize,
+    pub(crate) stamp: usize,
+    _tag: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    #[inline]
+    pub(crate) fn new(index: usize, stamp: usize) -> Self {
+        Self {
+            index,
+            stamp,
+            _tag: PhantomData,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the index the value was pushed at.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.index, self.stamp) == (other.index, other.stamp)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.index, self.stamp).hash(state)
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("type", &core::any::type_name::<T>())
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Returns a stamp that was never returned before, and is never 0.
+pub(crate) fn next_stamp() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(1);
+    NEXT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_add(1))
+        .expect("Stamps overflowed usize")
+}
@@ -1,11 +1,16 @@
-use std::{
-    intrinsics::{exact_div, unchecked_sub},
-    marker::PhantomData,
-    mem::size_of,
-    ptr::{self, NonNull},
-};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use core::ptr;
+use core::{iter::FusedIterator, marker::PhantomData, ptr::NonNull};
 
-use super::{FuseBox, Header};
+#[cfg(feature = "alloc")]
+use super::{compact::Compactor, move_to_box, FuseBox};
+use super::{
+    meta,
+    slice::{FuseSlice, FuseSliceMut},
+    Header,
+};
 
 macro_rules! is_empty {
     ($self:ident) => {
@@ -15,11 +20,12 @@ macro_rules! is_empty {
 
 macro_rules! len {
     ($self:ident) => {{
-        let start = $self.headers_ptr.as_ptr() as usize;
-        let size = size_of::<Header<Dyn>>();
-
-        let diff = unsafe { unchecked_sub($self.headers_end as usize, start) };
-        unsafe { exact_div(diff, size) }
+        // Safety: both pointers come from the same headers slice and start <= end
+        unsafe {
+            $self
+                .headers_end
+                .offset_from_unsigned($self.headers_ptr.as_ptr())
+        }
     }};
 }
 
@@ -32,18 +38,29 @@ macro_rules! impl_iter {
             headers_ptr: NonNull<Header<Dyn>>,
             headers_end: *const Header<Dyn>,
             data_base_ptr: NonNull<u8>,
-            _tag: PhantomData<&'f $($mut)? FuseBox<Dyn>>,
+            _tag: PhantomData<&'f $($mut)? Dyn>,
         }
 
         impl<'f, Dyn> $iter<'f, Dyn>
         where
             Dyn: ?Sized,
         {
+            #[cfg(feature = "alloc")]
             pub(crate) fn new(fused: &'f $($mut)? FuseBox<Dyn>) -> Self {
-                let headers_ptr =
-                    unsafe { NonNull::new_unchecked(fused.headers.as_ptr() as *mut _) };
-                let headers_end = unsafe { fused.headers.as_ptr().add(fused.headers.len()) };
-                let data_base_ptr = fused.inner;
+                // Safety: headers and inner come from the same FuseBox,
+                // which is borrowed for 'f
+                unsafe { Self::from_parts(&fused.headers, fused.bytes.inner) }
+            }
+
+            /// # Safety
+            /// Every header must describe a valid value inside `data_base_ptr`,
+            /// borrowable as `&'f Dyn` (or `&'f mut Dyn` for `IterMut`).
+            pub(crate) unsafe fn from_parts(
+                headers: &'f [Header<Dyn>],
+                data_base_ptr: NonNull<u8>,
+            ) -> Self {
+                let headers_ptr = unsafe { NonNull::new_unchecked(headers.as_ptr() as *mut _) };
+                let headers_end = unsafe { headers.as_ptr().add(headers.len()) };
                 Self {
                     headers_ptr,
                     headers_end,
@@ -68,11 +85,11 @@ macro_rules! impl_iter {
                     let next_ptr = self.headers_ptr.as_ptr();
                     let Header { offset, meta } = *next_ptr;
 
-                    let ptr = self.data_base_ptr.as_ptr().add(offset).cast();
+                    let ptr = self.data_base_ptr.as_ptr().add(offset);
 
                     self.headers_ptr = NonNull::new_unchecked(next_ptr.add(1));
 
-                    Some(&$($mut)? *ptr::from_raw_parts_mut::<Dyn>(ptr, meta))
+                    Some(&$($mut)? *meta::from_raw_parts_mut(ptr, meta))
                 }
             }
 
@@ -101,29 +118,57 @@ macro_rules! impl_iter {
                 unsafe {
                     let Header { offset, meta } = *self.headers_end.sub(1);
 
-                    let ptr = self.data_base_ptr.as_ptr().add(offset).cast();
-                    Some(& $($mut)? *ptr::from_raw_parts_mut::<Dyn>(ptr, meta))
+                    let ptr = self.data_base_ptr.as_ptr().add(offset);
+                    Some(& $($mut)? *meta::from_raw_parts_mut(ptr, meta))
                 }
             }
 
             #[inline]
             fn nth(&mut self, n: usize) -> Option<Self::Item> {
                 if n >= len!(self) {
+                    self.headers_ptr = unsafe { NonNull::new_unchecked(self.headers_end as *mut _) };
                     return None;
                 }
                 unsafe {
-                    let next_ptr = self.headers_ptr.as_ptr().add(n);
-                    let Header { offset, meta } = *next_ptr;
+                    self.headers_ptr = NonNull::new_unchecked(self.headers_ptr.as_ptr().add(n));
+                }
+                self.next()
+            }
+        }
 
-                    let ptr = self.data_base_ptr.as_ptr().add(offset).cast();
+        impl<'f, Dyn> DoubleEndedIterator for $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            #[inline]
+            fn next_back(&mut self) -> Option<Self::Item> {
+                if is_empty!(self) {
+                    return None;
+                }
+                unsafe {
+                    self.headers_end = self.headers_end.sub(1);
+                    let Header { offset, meta } = *self.headers_end;
 
-                    self.headers_ptr = NonNull::new_unchecked(next_ptr);
+                    let ptr = self.data_base_ptr.as_ptr().add(offset);
+                    Some(& $($mut)? *meta::from_raw_parts_mut(ptr, meta))
+                }
+            }
 
-                    Some(& $($mut)? *ptr::from_raw_parts_mut::<Dyn>(ptr, meta))
+            #[inline]
+            fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+                if n >= len!(self) {
+                    self.headers_end = self.headers_ptr.as_ptr();
+                    return None;
+                }
+                unsafe {
+                    self.headers_end = self.headers_end.sub(n);
                 }
+                self.next_back()
             }
         }
 
+        impl<'f, Dyn> FusedIterator for $iter<'f, Dyn> where Dyn: ?Sized {}
+
         impl<'f, Dyn> ExactSizeIterator for $iter<'f, Dyn>
         where
             Dyn: ?Sized,
@@ -138,3 +183,385 @@ macro_rules! impl_iter {
 
 impl_iter!(Iter);
 impl_iter!(IterMut, mut);
+
+macro_rules! impl_view_iter {
+    ($iter:ident, $inner:ident, $view:ident, $doc:literal) => {
+        #[doc = $doc]
+        pub struct $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            headers: core::slice::$inner<'f, Header<Dyn>>,
+            data_base_ptr: NonNull<u8>,
+        }
+
+        impl<'f, Dyn> $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            /// # Safety
+            /// Every yielded view must satisfy the safety contract of its `from_parts`.
+            pub(crate) unsafe fn from_parts(
+                headers: core::slice::$inner<'f, Header<Dyn>>,
+                data_base_ptr: NonNull<u8>,
+            ) -> Self {
+                Self {
+                    headers,
+                    data_base_ptr,
+                }
+            }
+        }
+
+        impl<'f, Dyn> Iterator for $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            type Item = $view<'f, Dyn>;
+
+            #[inline]
+            fn next(&mut self) -> Option<Self::Item> {
+                let headers = self.headers.next()?;
+                // Safety: views never overlap unless they are shared
+                unsafe { Some($view::from_parts(headers, self.data_base_ptr)) }
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.headers.size_hint()
+            }
+        }
+
+        impl<'f, Dyn> DoubleEndedIterator for $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            #[inline]
+            fn next_back(&mut self) -> Option<Self::Item> {
+                let headers = self.headers.next_back()?;
+                unsafe { Some($view::from_parts(headers, self.data_base_ptr)) }
+            }
+        }
+
+        impl<'f, Dyn> ExactSizeIterator for $iter<'f, Dyn> where Dyn: ?Sized {}
+
+        impl<'f, Dyn> FusedIterator for $iter<'f, Dyn> where Dyn: ?Sized {}
+    };
+}
+
+impl_view_iter!(
+    Windows,
+    Windows,
+    FuseSlice,
+    "Iterator over overlapping views of `size` elements, see [`FuseSlice::windows`]"
+);
+impl_view_iter!(
+    Chunks,
+    Chunks,
+    FuseSlice,
+    "Iterator over views of `size` elements, see [`FuseSlice::chunks`]"
+);
+impl_view_iter!(
+    ChunksMut,
+    Chunks,
+    FuseSliceMut,
+    "Iterator over mutable views of `size` elements, see [`FuseSliceMut::chunks_mut`]"
+);
+
+/// Lending iterator over neighboring elements, see [`FuseSliceMut::pairs_mut`]
+///
+/// Pairs overlap, so this can't be an [`Iterator`]. Use `while let` instead:
+///
+/// ```
+/// # #[cfg(feature = "alloc")] {
+/// # use fusebox::{fuse_push, FuseBox};
+/// # use std::ops::AddAssign;
+/// let mut fb = FuseBox::<dyn AddAssign<u32>>::new();
+/// fuse_push!(fb, 1u32);
+/// fuse_push!(fb, 2u32);
+/// fuse_push!(fb, 3u32);
+/// let mut pairs = fb.pairs_mut();
+/// while let Some((a, b)) = pairs.next() {
+///     *a += 1;
+///     *b += 1;
+/// }
+/// # }
+/// ```
+pub struct PairsMut<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    headers: &'f [Header<Dyn>],
+    data_base_ptr: NonNull<u8>,
+}
+
+impl<'f, Dyn> PairsMut<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// # Safety
+    /// Same as [`FuseSliceMut::from_parts`].
+    pub(crate) unsafe fn from_parts(
+        headers: &'f [Header<Dyn>],
+        data_base_ptr: NonNull<u8>,
+    ) -> Self {
+        Self {
+            headers,
+            data_base_ptr,
+        }
+    }
+
+    /// Returns the next pair of neighbors, advancing by one element.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&mut Dyn, &mut Dyn)> {
+        let [Header {
+            offset: a,
+            meta: meta_a,
+        }, Header {
+            offset: b,
+            meta: meta_b,
+        }, ..] = *self.headers
+        else {
+            return None;
+        };
+        self.headers = &self.headers[1..];
+        let base = self.data_base_ptr.as_ptr();
+        // Safety: two distinct elements, borrowed until the next call
+        unsafe {
+            Some((
+                &mut *meta::from_raw_parts_mut(base.add(a), meta_a),
+                &mut *meta::from_raw_parts_mut(base.add(b), meta_b),
+            ))
+        }
+    }
+
+    /// Returns the number of pairs left.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.headers.len().saturating_sub(1)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Owning iterator that moves every value of a [`FuseBox`] into its own [`Box`]
+#[cfg(feature = "alloc")]
+pub struct IntoIter<Dyn>
+where
+    Dyn: ?Sized,
+{
+    fused: FuseBox<Dyn>,
+    front: usize,
+    back: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> IntoIter<Dyn>
+where
+    Dyn: ?Sized,
+{
+    pub(crate) fn new(fused: FuseBox<Dyn>) -> Self {
+        let back = fused.len();
+        Self {
+            fused,
+            front: 0,
+            back,
+        }
+    }
+
+    /// # Safety
+    /// `n` must be in `front..back` and removed from that range right after.
+    #[inline]
+    unsafe fn take(&mut self, n: usize) -> Box<Dyn> {
+        let Header { offset, meta } = self.fused.headers[n];
+        let layout = self.fused.layout_of(n);
+        unsafe { move_to_box(self.fused.bytes.inner.as_ptr().add(offset), layout, meta) }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> Drop for IntoIter<Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        // FuseBox must only free the memory, even if a destructor panics
+        let headers = self.fused.headers.as_ptr();
+        unsafe { self.fused.headers.set_len(0) };
+        // Safety: values in front..back have not been moved out yet
+        unsafe {
+            for n in self.front..self.back {
+                let Header { offset, meta } = *headers.add(n);
+                let ptr = self.fused.bytes.inner.as_ptr().add(offset);
+                ptr::drop_in_place(meta::from_raw_parts_mut::<Dyn>(ptr, meta));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> Iterator for IntoIter<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = Box<Dyn>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        let n = self.front;
+        self.front += 1;
+        unsafe { Some(self.take(n)) }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> DoubleEndedIterator for IntoIter<Dyn>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        unsafe { Some(self.take(self.back)) }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> ExactSizeIterator for IntoIter<Dyn> where Dyn: ?Sized {}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> FusedIterator for IntoIter<Dyn> where Dyn: ?Sized {}
+
+/// Draining iterator returned by [`FuseBox::drain`]
+///
+/// Elements of the range that are not consumed are dropped along with the iterator.
+#[cfg(feature = "alloc")]
+pub struct Drain<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    compactor: Compactor<'f, Dyn>,
+    end: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<'f, Dyn> Drain<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    pub(crate) fn new(fused: &'f mut FuseBox<Dyn>, start: usize, end: usize) -> Self {
+        Self {
+            compactor: Compactor::new(fused, start),
+            end,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> Drop for Drain<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        while self.compactor.processed() < self.end {
+            // Safety: the element is in the drained range
+            unsafe { ptr::drop_in_place(self.compactor.remove_next()) };
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> Iterator for Drain<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = Box<Dyn>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.compactor.processed() == self.end {
+            return None;
+        }
+        // Safety: the element is in the drained range
+        unsafe { Some(self.compactor.remove_next_boxed()) }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.compactor.processed();
+        (len, Some(len))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> ExactSizeIterator for Drain<'_, Dyn> where Dyn: ?Sized {}
+
+#[cfg(feature = "alloc")]
+impl<Dyn> FusedIterator for Drain<'_, Dyn> where Dyn: ?Sized {}
+
+/// Iterator returned by [`FuseBox::extract_if`]
+///
+/// Elements not visited yet are kept when the iterator is dropped.
+#[cfg(feature = "alloc")]
+pub struct ExtractIf<'f, Dyn, F>
+where
+    Dyn: ?Sized,
+{
+    compactor: Compactor<'f, Dyn>,
+    pred: F,
+}
+
+#[cfg(feature = "alloc")]
+impl<'f, Dyn, F> ExtractIf<'f, Dyn, F>
+where
+    Dyn: ?Sized,
+{
+    pub(crate) fn new(fused: &'f mut FuseBox<Dyn>, pred: F) -> Self {
+        Self {
+            compactor: Compactor::new(fused, 0),
+            pred,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn, F> Iterator for ExtractIf<'_, Dyn, F>
+where
+    Dyn: ?Sized,
+    F: FnMut(&mut Dyn) -> bool,
+{
+    type Item = Box<Dyn>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(v) = self.compactor.next_ptr() {
+            // Safety: the value stays valid until it's kept or removed
+            if (self.pred)(unsafe { &mut *v }) {
+                return unsafe { Some(self.compactor.remove_next_boxed()) };
+            }
+            self.compactor.keep_next();
+        }
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Dyn, F> FusedIterator for ExtractIf<'_, Dyn, F>
+where
+    Dyn: ?Sized,
+    F: FnMut(&mut Dyn) -> bool,
+{
+}
@@ -0,0 +1,159 @@
+//! Pointer metadata backends
+//!
+//! With the `nightly` feature metadata is [`Pointee::Metadata`](core::ptr::Pointee::Metadata).
+//! Without it, metadata is a function that performs the unsizing coercion for one concrete
+//! type, captured at push time by [`fuse_push!`](crate::fuse_push).
+//! Such metadata can't be recovered from an existing `*mut Dyn`,
+//! so APIs taking `Box<Dyn>` require the `nightly` feature.
+
+#[cfg(feature = "nightly")]
+mod imp {
+    use core::ptr::{self, Pointee};
+
+    /// Metadata stored for every element of a `Dyn` collection.
+    pub type Metadata<Dyn> = <Dyn as Pointee>::Metadata;
+
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`.
+    #[inline]
+    pub(crate) unsafe fn from_coerce<T, Dyn>(coerce: fn(*mut T) -> *mut Dyn) -> Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        ptr::metadata(coerce(ptr::null_mut()))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn from_ptr<Dyn>(ptr: *const Dyn) -> Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        ptr::metadata(ptr)
+    }
+
+    #[inline]
+    pub(crate) fn from_raw_parts_mut<Dyn>(data: *mut u8, meta: Metadata<Dyn>) -> *mut Dyn
+    where
+        Dyn: ?Sized,
+    {
+        ptr::from_raw_parts_mut(data, meta)
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+mod imp {
+    use core::{
+        cmp::Ordering,
+        fmt,
+        hash::{Hash, Hasher},
+        mem,
+    };
+
+    /// Metadata stored for every element of a `Dyn` collection.
+    ///
+    /// Two values of the same type usually, but not always, share the same metadata.
+    pub struct Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        coerce: fn(*mut u8) -> *mut Dyn,
+    }
+
+    impl<Dyn> Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        #[inline]
+        fn addr(self) -> usize {
+            self.coerce as usize
+        }
+    }
+
+    impl<Dyn> Clone for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<Dyn> Copy for Metadata<Dyn> where Dyn: ?Sized {}
+
+    impl<Dyn> PartialEq for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn eq(&self, other: &Self) -> bool {
+            self.addr() == other.addr()
+        }
+    }
+
+    impl<Dyn> Eq for Metadata<Dyn> where Dyn: ?Sized {}
+
+    impl<Dyn> PartialOrd for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl<Dyn> Ord for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.addr().cmp(&other.addr())
+        }
+    }
+
+    impl<Dyn> Hash for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.addr().hash(state)
+        }
+    }
+
+    impl<Dyn> fmt::Debug for Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("Metadata").field(&self.addr()).finish()
+        }
+    }
+
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`.
+    #[inline]
+    pub(crate) unsafe fn from_coerce<T, Dyn>(coerce: fn(*mut T) -> *mut Dyn) -> Metadata<Dyn>
+    where
+        Dyn: ?Sized,
+    {
+        // Safety: thin pointers are ABI-compatible with each other,
+        // so the function may be called with `*mut u8`
+        Metadata {
+            coerce: unsafe {
+                mem::transmute::<fn(*mut T) -> *mut Dyn, fn(*mut u8) -> *mut Dyn>(coerce)
+            },
+        }
+    }
+
+    #[inline]
+    pub(crate) fn from_raw_parts_mut<Dyn>(data: *mut u8, meta: Metadata<Dyn>) -> *mut Dyn
+    where
+        Dyn: ?Sized,
+    {
+        (meta.coerce)(data)
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+pub(crate) use imp::from_ptr;
+pub use imp::Metadata;
+pub(crate) use imp::{from_coerce, from_raw_parts_mut};
@@ -0,0 +1,471 @@
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "nightly")]
+use core::marker::Unsize;
+use core::{
+    alloc::Layout,
+    iter::{Enumerate, FusedIterator},
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Index, IndexMut},
+    ptr::{self, drop_in_place, NonNull},
+    slice,
+};
+
+use super::{
+    bytes::RawBytes,
+    handle::next_stamp,
+    meta::{self, Metadata},
+    move_to_box, round_up,
+};
+
+/// Key of a value in a [`FuseSlab`]
+///
+/// Stays valid until that value is removed, and never resolves to another value afterwards,
+/// nor to a value in another [`FuseSlab`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    slab: usize,
+    index: usize,
+    generation: usize,
+}
+
+impl Key {
+    #[must_use]
+    #[inline]
+    /// Returns the index of the slot holding the value.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+struct Slot<Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// Start of the bytes reserved for this slot.
+    start: usize,
+    /// Number of bytes reserved for this slot.
+    size: usize,
+    /// Offset of the value, at or after `start`.
+    offset: usize,
+    /// Bumped every time the slot is freed.
+    generation: usize,
+    /// `None` if the slot is free.
+    meta: Option<Metadata<Dyn>>,
+}
+
+/// Number of free lists, one per alignment of a hole's start up to `1 << (FREE_LISTS - 1)`.
+const FREE_LISTS: usize = 8;
+
+/// Returns the free list for a hole starting at `start`.
+#[inline]
+fn free_list(start: usize) -> usize {
+    (start.trailing_zeros() as usize).min(FREE_LISTS - 1)
+}
+
+/// Contigous type-erased storage with stable keys
+///
+/// Removing a value leaves a hole instead of moving later values,
+/// and later pushes reuse holes their value fits in.
+/// Holes are kept in free lists by alignment, so a value is placed in a hole
+/// that needs no padding first.
+///
+/// `Dyn` shall be `dyn Trait`
+///
+/// ```
+/// # use std::fmt::Debug;
+/// # use fusebox::{fuse_push, FuseSlab};
+/// let mut slab = FuseSlab::<dyn Debug>::new();
+/// let a = fuse_push!(slab, 1u32);
+/// let b = fuse_push!(slab, "two");
+/// assert!(slab.remove(a));
+/// assert!(slab.get(a).is_none());
+/// let c = fuse_push!(slab, 3u16);
+/// assert_eq!(c.index(), a.index());
+/// assert!(slab.get(a).is_none());
+/// assert_eq!(format!("{:?}", &slab[b]), "\"two\"");
+/// ```
+pub struct FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    slots: Vec<Slot<Dyn>>,
+    free: [Vec<usize>; FREE_LISTS],
+    /// Size of the largest hole in each free list, so lists without room are skipped.
+    largest: [usize; FREE_LISTS],
+    len: usize,
+    bytes: RawBytes,
+    /// Unique among all slabs, 0 until the first push.
+    id: usize,
+}
+
+impl<Dyn> Default for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Dyn> Drop for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        // Safety:
+        // occupied slots hold valid values
+        unsafe {
+            for (_, val) in self.iter_mut() {
+                drop_in_place(val);
+            }
+        }
+    }
+}
+
+unsafe impl<Dyn> Send for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Send,
+{
+}
+
+unsafe impl<Dyn> Sync for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+impl<Dyn> FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    #[must_use]
+    /// Creates a new [`FuseSlab<Dyn>`].
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: [const { Vec::new() }; FREE_LISTS],
+            largest: [0; FREE_LISTS],
+            len: 0,
+            bytes: RawBytes::new(),
+            id: 0,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the number of values in this [`FuseSlab<Dyn>`].
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finds a free slot `layout` fits in, returning its free list, position there and value offset.
+    ///
+    /// Lists of holes aligned for `layout` are searched first, from the least aligned up,
+    /// then the ones that need padding.
+    fn find_free(&self, layout: Layout) -> Option<(usize, usize, usize)> {
+        if layout.align() > self.bytes.max_align || self.holes() == 0 {
+            return None;
+        }
+        let aligned = free_list(layout.align());
+        (aligned..FREE_LISTS)
+            .chain((0..aligned).rev())
+            .filter(|&list| self.largest[list] >= layout.size())
+            .find_map(|list| {
+                self.free[list].iter().enumerate().find_map(|(pos, &n)| {
+                    let Slot { start, size, .. } = self.slots[n];
+                    let offset = round_up(start, layout.align());
+                    (offset + layout.size() <= start + size).then_some((list, pos, offset))
+                })
+            })
+    }
+
+    /// Takes the hole at `pos` out of free list `list`, returning its slot.
+    fn take_free(&mut self, list: usize, pos: usize) -> usize {
+        let n = self.free[list].swap_remove(pos);
+        if self.slots[n].size == self.largest[list] {
+            self.largest[list] = self.free[list]
+                .iter()
+                .map(|&n| self.slots[n].size)
+                .max()
+                .unwrap_or(0);
+        }
+        n
+    }
+
+    /// Returns the number of free slots.
+    #[inline]
+    fn holes(&self) -> usize {
+        self.slots.len() - self.len
+    }
+
+    /// Bitwise moves a value described by `layout` and `meta` from `src` into the [`FuseSlab`].
+    ///
+    /// # Safety
+    /// `src` must point to a valid value of that layout and metadata,
+    /// which the caller must not use or drop afterwards.
+    unsafe fn push_raw(&mut self, src: *const u8, layout: Layout, meta: Metadata<Dyn>) -> Key {
+        if self.id == 0 {
+            self.id = next_stamp();
+        }
+        let index = if let Some((list, pos, offset)) = self.find_free(layout) {
+            let n = self.take_free(list, pos);
+            let slot = &mut self.slots[n];
+            slot.offset = offset;
+            slot.meta = Some(meta);
+            n
+        } else {
+            let start = self.bytes.len_bytes;
+            let offset = round_up(start, layout.align());
+            self.slots.reserve(1);
+            self.bytes
+                .reserve_bytes(offset + layout.size(), layout.align());
+            self.slots.push(Slot {
+                start,
+                size: offset + layout.size() - start,
+                offset,
+                generation: 0,
+                meta: Some(meta),
+            });
+            self.bytes.len_bytes = offset + layout.size();
+            self.slots.len() - 1
+        };
+
+        let slot = &self.slots[index];
+        // Safety: the slot is in-bounds and aligned for the value
+        unsafe {
+            ptr::copy_nonoverlapping(
+                src,
+                self.bytes.inner.as_ptr().add(slot.offset),
+                layout.size(),
+            );
+        }
+        self.len += 1;
+        Key {
+            slab: self.id,
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    #[inline]
+    /// Inserts a value, returning its [`Key`].
+    pub fn push<T>(&mut self, v: T) -> Key
+    where
+        T: 'static,
+        T: Unsize<Dyn>,
+        Dyn: 'static,
+    {
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce(v, |p| p) }
+    }
+
+    #[inline]
+    /// Inserts a value using `coerce` to unsize it, returning its [`Key`].
+    ///
+    /// See [`FuseBox::push_coerce`](crate::FuseBox::push_coerce).
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce<T>(&mut self, v: T, coerce: fn(*mut T) -> *mut Dyn) -> Key
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        let meta = unsafe { meta::from_coerce(coerce) };
+        let v = ManuallyDrop::new(v);
+        unsafe { self.push_raw((&*v as *const T).cast(), Layout::new::<T>(), meta) }
+    }
+
+    #[inline]
+    fn get_raw(&self, key: Key) -> Option<*mut Dyn> {
+        if key.slab != self.id {
+            return None;
+        }
+        let slot = self.slots.get(key.index)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+        let meta = slot.meta?;
+        Some(meta::from_raw_parts_mut(
+            unsafe { self.bytes.inner.as_ptr().add(slot.offset) },
+            meta,
+        ))
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns `true` if `key` refers to a value in this [`FuseSlab`].
+    pub fn contains(&self, key: Key) -> bool {
+        self.get_raw(key).is_some()
+    }
+
+    #[must_use]
+    #[inline]
+    /// Retrieves `&Dyn` from [`FuseSlab`], or `None` if it was removed.
+    pub fn get(&self, key: Key) -> Option<&Dyn> {
+        self.get_raw(key).map(|v| unsafe { &*v })
+    }
+
+    #[inline]
+    /// Retrieves `&mut Dyn` from [`FuseSlab`], or `None` if it was removed.
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut Dyn> {
+        self.get_raw(key).map(|v| unsafe { &mut *v })
+    }
+
+    /// Marks the slot of `key` as free, returning the value it held.
+    ///
+    /// The caller must move out or drop the value.
+    fn release(&mut self, key: Key) -> Option<(*mut u8, Metadata<Dyn>)> {
+        self.get_raw(key)?;
+        let slot = &mut self.slots[key.index];
+        let meta = slot.meta.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        let src = unsafe { self.bytes.inner.as_ptr().add(slot.offset) };
+        let list = free_list(slot.start);
+        self.free[list].push(key.index);
+        self.largest[list] = self.largest[list].max(slot.size);
+        self.len -= 1;
+        Some((src, meta))
+    }
+
+    /// Drops the value of `key`, leaving a hole for later pushes.
+    ///
+    /// Returns `false` if it was already removed.
+    pub fn remove(&mut self, key: Key) -> bool {
+        let Some((src, meta)) = self.release(key) else {
+            return false;
+        };
+        unsafe { drop_in_place(meta::from_raw_parts_mut::<Dyn>(src, meta)) };
+        true
+    }
+
+    /// Moves the value of `key` into its own [`Box`], leaving a hole for later pushes.
+    pub fn take(&mut self, key: Key) -> Option<Box<Dyn>> {
+        let (src, meta) = self.release(key)?;
+        unsafe {
+            let layout = Layout::for_value(&*meta::from_raw_parts_mut::<Dyn>(src, meta));
+            Some(move_to_box(src, layout, meta))
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over the keys and values in this [`FuseSlab`].
+    pub fn iter(&self) -> SlabIter<'_, Dyn> {
+        SlabIter {
+            slots: self.slots.iter().enumerate(),
+            base: self.bytes.inner,
+            slab: self.id,
+            remaining: self.len,
+            dense: self.holes() == 0,
+            _tag: PhantomData,
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over the keys and mutable values in this [`FuseSlab`].
+    pub fn iter_mut(&mut self) -> SlabIterMut<'_, Dyn> {
+        SlabIterMut {
+            slots: self.slots.iter().enumerate(),
+            base: self.bytes.inner,
+            slab: self.id,
+            remaining: self.len,
+            dense: self.holes() == 0,
+            _tag: PhantomData,
+        }
+    }
+}
+
+impl<Dyn> Index<Key> for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, key: Key) -> &Self::Output {
+        self.get(key).expect("invalid key")
+    }
+}
+
+impl<Dyn> IndexMut<Key> for FuseSlab<Dyn>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, key: Key) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid key")
+    }
+}
+
+macro_rules! impl_slab_iter {
+    ($iter:ident $(, $mut:tt)?) => {
+        /// Iterator over the values of a [`FuseSlab`] and their keys
+        ///
+        /// Without holes every slot is yielded unchecked,
+        /// otherwise holes are skipped and iteration stops after the last value.
+        pub struct $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            slots: Enumerate<slice::Iter<'f, Slot<Dyn>>>,
+            base: NonNull<u8>,
+            slab: usize,
+            remaining: usize,
+            dense: bool,
+            _tag: PhantomData<&'f $($mut)? Dyn>,
+        }
+
+        impl<'f, Dyn> Iterator for $iter<'f, Dyn>
+        where
+            Dyn: ?Sized,
+        {
+            type Item = (Key, &'f $($mut)? Dyn);
+
+            #[inline]
+            fn next(&mut self) -> Option<Self::Item> {
+                if self.remaining == 0 {
+                    return None;
+                }
+                let (index, slot) = if self.dense {
+                    self.slots.next()?
+                } else {
+                    self.slots.find(|(_, slot)| slot.meta.is_some())?
+                };
+                self.remaining -= 1;
+                // Safety: the slot is occupied, and its value is borrowed for 'f
+                unsafe {
+                    let meta = slot.meta.unwrap_unchecked();
+                    let ptr = meta::from_raw_parts_mut(self.base.as_ptr().add(slot.offset), meta);
+                    let key = Key {
+                        slab: self.slab,
+                        index,
+                        generation: slot.generation,
+                    };
+                    Some((key, & $($mut)? *ptr))
+                }
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.remaining, Some(self.remaining))
+            }
+        }
+
+        impl<'f, Dyn> ExactSizeIterator for $iter<'f, Dyn> where Dyn: ?Sized {}
+
+        impl<'f, Dyn> FusedIterator for $iter<'f, Dyn> where Dyn: ?Sized {}
+    };
+}
+
+impl_slab_iter!(SlabIter);
+impl_slab_iter!(SlabIterMut, mut);
@@ -0,0 +1,465 @@
+use core::{
+    marker::PhantomData,
+    ops::{Index, IndexMut, Range, RangeBounds},
+    ptr::NonNull,
+    slice::GetDisjointMutError,
+};
+
+use super::{
+    iter::{Chunks, ChunksMut, Iter, IterMut, PairsMut, Windows},
+    meta, to_range, Header,
+};
+
+/// Borrowed view of a range of elements, like `&[T]` for a [`FuseBox`](crate::FuseBox)
+///
+/// [`Index`] can only return a reference, so views are made with
+/// [`FuseBox::slice`](crate::FuseBox::slice) rather than `&fb[a..b]`.
+///
+/// ```
+/// # #[cfg(feature = "alloc")] {
+/// # use std::fmt::Debug;
+/// # use fusebox::{fuse_push, FuseBox};
+/// let mut fb = FuseBox::<dyn Debug>::new();
+/// fuse_push!(fb, 1u8);
+/// fuse_push!(fb, 2u64);
+/// fuse_push!(fb, "three");
+/// let slice = fb.slice(1..);
+/// assert_eq!(slice.len(), 2);
+/// assert_eq!(format!("{:?}", slice.last().unwrap()), "\"three\"");
+/// assert_eq!(format!("{:?}", &slice.slice(..1)[0]), "2");
+/// # }
+/// ```
+pub struct FuseSlice<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    headers: &'f [Header<Dyn>],
+    base: NonNull<u8>,
+}
+
+/// Mutably borrowed view of a range of elements, like `&mut [T]` for a [`FuseBox`](crate::FuseBox)
+///
+/// Made with [`FuseBox::slice_mut`](crate::FuseBox::slice_mut).
+pub struct FuseSliceMut<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    headers: &'f [Header<Dyn>],
+    base: NonNull<u8>,
+    _tag: PhantomData<&'f mut Dyn>,
+}
+
+impl<Dyn> Clone for FuseSlice<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Dyn> Copy for FuseSlice<'_, Dyn> where Dyn: ?Sized {}
+
+unsafe impl<Dyn> Send for FuseSlice<'_, Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+unsafe impl<Dyn> Sync for FuseSlice<'_, Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+unsafe impl<Dyn> Send for FuseSliceMut<'_, Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Send,
+{
+}
+
+unsafe impl<Dyn> Sync for FuseSliceMut<'_, Dyn>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+impl<'f, Dyn> FuseSlice<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// # Safety
+    /// Every header must describe a valid value inside `base`, borrowable as `&'f Dyn`.
+    #[inline]
+    pub(crate) unsafe fn from_parts(headers: &'f [Header<Dyn>], base: NonNull<u8>) -> Self {
+        Self { headers, base }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the length of this [`FuseSlice`] in items.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Retrieves `&Dyn` from [`FuseSlice`].
+    pub fn get(&self, n: usize) -> Option<&'f Dyn> {
+        let &Header { offset, meta } = self.headers.get(n)?;
+        unsafe {
+            Some(&*meta::from_raw_parts_mut(
+                self.base.as_ptr().add(offset),
+                meta,
+            ))
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn first(&self) -> Option<&'f Dyn> {
+        self.get(0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn last(&self) -> Option<&'f Dyn> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&Dyn` in this [`FuseSlice`].
+    pub fn iter(&self) -> Iter<'f, Dyn> {
+        // Safety: same headers and base as self
+        unsafe { Iter::from_parts(self.headers, self.base) }
+    }
+
+    #[must_use]
+    /// Returns a narrower view of the elements in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn slice<R>(&self, range: R) -> FuseSlice<'f, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        let Range { start, end } = to_range(range, self.len());
+        Self {
+            headers: &self.headers[start..end],
+            base: self.base,
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over all overlapping views of `size` elements.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn windows(&self, size: usize) -> Windows<'f, Dyn> {
+        // Safety: windows are shared views
+        unsafe { Windows::from_parts(self.headers.windows(size), self.base) }
+    }
+
+    #[must_use]
+    /// Returns an iterator over views of `size` elements, the last one may be shorter.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn chunks(&self, size: usize) -> Chunks<'f, Dyn> {
+        // Safety: chunks are shared views
+        unsafe { Chunks::from_parts(self.headers.chunks(size), self.base) }
+    }
+}
+
+impl<'f, Dyn> FuseSliceMut<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    /// # Safety
+    /// Every header must describe a valid value inside `base`, borrowable as `&'f mut Dyn`.
+    #[inline]
+    pub(crate) unsafe fn from_parts(headers: &'f [Header<Dyn>], base: NonNull<u8>) -> Self {
+        Self {
+            headers,
+            base,
+            _tag: PhantomData,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the length of this [`FuseSliceMut`] in items.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    /// Reborrows as a shared [`FuseSlice`].
+    pub fn as_slice(&self) -> FuseSlice<'_, Dyn> {
+        // Safety: self is borrowed
+        unsafe { FuseSlice::from_parts(self.headers, self.base) }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Reborrows as a shorter-lived [`FuseSliceMut`].
+    pub fn reborrow(&mut self) -> FuseSliceMut<'_, Dyn> {
+        // Safety: self is borrowed mutably
+        unsafe { FuseSliceMut::from_parts(self.headers, self.base) }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Retrieves `&Dyn` from [`FuseSliceMut`].
+    pub fn get(&self, n: usize) -> Option<&Dyn> {
+        self.as_slice().get(n)
+    }
+
+    /// # Safety
+    /// `n` must be in-bounds.
+    #[inline]
+    unsafe fn get_raw(&self, n: usize) -> *mut Dyn {
+        let Header { offset, meta } = unsafe { *self.headers.get_unchecked(n) };
+        unsafe { meta::from_raw_parts_mut(self.base.as_ptr().add(offset), meta) }
+    }
+
+    #[inline]
+    /// Retrieves `&mut Dyn` from [`FuseSliceMut`].
+    pub fn get_mut(&mut self, n: usize) -> Option<&mut Dyn> {
+        if self.len() <= n {
+            return None;
+        }
+        unsafe { Some(&mut *self.get_raw(n)) }
+    }
+
+    /// Returns mutable references to several elements at once.
+    ///
+    /// Fails if any index is out of bounds or appears twice.
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Result<[&mut Dyn; N], GetDisjointMutError> {
+        self.reborrow().into_disjoint_mut(indices)
+    }
+
+    #[must_use]
+    /// Divides the view into two at `mid`.
+    ///
+    /// # Panics
+    /// Panics if `mid` is greater than the length.
+    pub fn split_at_mut(&mut self, mid: usize) -> (FuseSliceMut<'_, Dyn>, FuseSliceMut<'_, Dyn>) {
+        self.reborrow().into_split_at(mid)
+    }
+
+    /// Returns the first element and a view of the rest, or `None` if empty.
+    pub fn split_first_mut(&mut self) -> Option<(&mut Dyn, FuseSliceMut<'_, Dyn>)> {
+        self.reborrow().into_split_first()
+    }
+
+    /// Returns the last element and a view of the rest, or `None` if empty.
+    pub fn split_last_mut(&mut self) -> Option<(&mut Dyn, FuseSliceMut<'_, Dyn>)> {
+        self.reborrow().into_split_last()
+    }
+
+    #[must_use]
+    /// Returns an iterator over mutable views of `size` elements, the last one may be shorter.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0.
+    pub fn chunks_mut(&mut self, size: usize) -> ChunksMut<'_, Dyn> {
+        self.reborrow().into_chunks_mut(size)
+    }
+
+    #[must_use]
+    /// Returns a lending iterator over `(&mut Dyn, &mut Dyn)` for every pair of neighbors.
+    pub fn pairs_mut(&mut self) -> PairsMut<'_, Dyn> {
+        self.reborrow().into_pairs_mut()
+    }
+
+    pub(crate) fn into_chunks_mut(self, size: usize) -> ChunksMut<'f, Dyn> {
+        // Safety: chunks are disjoint
+        unsafe { ChunksMut::from_parts(self.headers.chunks(size), self.base) }
+    }
+
+    pub(crate) fn into_pairs_mut(self) -> PairsMut<'f, Dyn> {
+        // Safety: self is consumed
+        unsafe { PairsMut::from_parts(self.headers, self.base) }
+    }
+
+    pub(crate) fn into_disjoint_mut<const N: usize>(
+        self,
+        indices: [usize; N],
+    ) -> Result<[&'f mut Dyn; N], GetDisjointMutError> {
+        for (i, &n) in indices.iter().enumerate() {
+            if n >= self.len() {
+                return Err(GetDisjointMutError::IndexOutOfBounds);
+            }
+            if indices[..i].contains(&n) {
+                return Err(GetDisjointMutError::OverlappingIndices);
+            }
+        }
+        // Safety: indices are in-bounds and unique
+        Ok(indices.map(|n| unsafe { &mut *self.get_raw(n) }))
+    }
+
+    pub(crate) fn into_split_at(self, mid: usize) -> (Self, Self) {
+        let (a, b) = self.headers.split_at(mid);
+        // Safety: the halves are disjoint
+        unsafe {
+            (
+                Self::from_parts(a, self.base),
+                Self::from_parts(b, self.base),
+            )
+        }
+    }
+
+    pub(crate) fn into_split_first(self) -> Option<(&'f mut Dyn, Self)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (first, rest) = self.into_split_at(1);
+        Some((unsafe { &mut *first.get_raw(0) }, rest))
+    }
+
+    pub(crate) fn into_split_last(self) -> Option<(&'f mut Dyn, Self)> {
+        let mid = self.len().checked_sub(1)?;
+        let (rest, last) = self.into_split_at(mid);
+        Some((unsafe { &mut *last.get_raw(0) }, rest))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn first(&self) -> Option<&Dyn> {
+        self.get(0)
+    }
+
+    #[inline]
+    pub fn first_mut(&mut self) -> Option<&mut Dyn> {
+        self.get_mut(0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn last(&self) -> Option<&Dyn> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    #[inline]
+    pub fn last_mut(&mut self) -> Option<&mut Dyn> {
+        self.get_mut(self.len().checked_sub(1)?)
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&Dyn` in this [`FuseSliceMut`].
+    pub fn iter(&self) -> Iter<'_, Dyn> {
+        self.as_slice().iter()
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&mut Dyn` in this [`FuseSliceMut`].
+    pub fn iter_mut(&mut self) -> IterMut<'_, Dyn> {
+        self.reborrow().into_iter()
+    }
+
+    #[must_use]
+    /// Returns a shared view of the elements in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn slice<R>(&self, range: R) -> FuseSlice<'_, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        self.as_slice().slice(range)
+    }
+
+    #[must_use]
+    /// Returns a narrower mutable view of the elements in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn slice_mut<R>(&mut self, range: R) -> FuseSliceMut<'_, Dyn>
+    where
+        R: RangeBounds<usize>,
+    {
+        let Range { start, end } = to_range(range, self.len());
+        // Safety: self is borrowed mutably
+        unsafe { FuseSliceMut::from_parts(&self.headers[start..end], self.base) }
+    }
+}
+
+impl<Dyn> Index<usize> for FuseSlice<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<Dyn> Index<usize> for FuseSliceMut<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<Dyn> IndexMut<usize> for FuseSliceMut<'_, Dyn>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<'f, Dyn> IntoIterator for FuseSlice<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = &'f Dyn;
+    type IntoIter = Iter<'f, Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'f, Dyn> IntoIterator for FuseSliceMut<'f, Dyn>
+where
+    Dyn: ?Sized,
+{
+    type Item = &'f mut Dyn;
+    type IntoIter = IterMut<'f, Dyn>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        // Safety: self is consumed, so the borrow lasts for 'f
+        unsafe { IterMut::from_parts(self.headers, self.base) }
+    }
+}
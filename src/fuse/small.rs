@@ -0,0 +1,208 @@
+#[cfg(feature = "nightly")]
+use core::marker::Unsize;
+use core::{
+    alloc::Layout,
+    mem::{self, ManuallyDrop},
+    ops::{Index, IndexMut},
+};
+
+use super::{
+    array::ArrayFuseBox,
+    iter::{Iter, IterMut},
+    meta, FuseBox, Header,
+};
+
+enum Repr<Dyn, const N: usize, const H: usize>
+where
+    Dyn: ?Sized,
+{
+    Inline(ArrayFuseBox<Dyn, N, H>),
+    Heap(FuseBox<Dyn>),
+}
+
+/// Moves every value of `inline` into a fresh [`FuseBox`], leaving it empty.
+fn spill<Dyn, const N: usize, const H: usize>(inline: &mut ArrayFuseBox<Dyn, N, H>) -> FuseBox<Dyn>
+where
+    Dyn: ?Sized,
+{
+    // If moving panics the rest is leaked rather than dropped twice
+    let mut inline = ManuallyDrop::new(mem::take(inline));
+    let base = inline.base_mut().as_ptr();
+    let mut heap = FuseBox::new();
+    for &Header { offset, meta } in inline.headers() {
+        unsafe {
+            let src = base.add(offset);
+            let layout = Layout::for_value(&*meta::from_raw_parts_mut::<Dyn>(src, meta));
+            heap.push_raw(src, layout, meta);
+        }
+    }
+    heap
+}
+
+/// [`FuseBox`] that stores up to `N` bytes of values and `H` headers inline
+///
+/// Starts out as an [`ArrayFuseBox`]. Once a value does not fit, everything is moved to the heap.
+/// Values aligned to more than 16 bytes always live on the heap.
+///
+/// `Dyn` shall be `dyn Trait`
+pub struct SmallFuseBox<Dyn, const N: usize, const H: usize = 8>
+where
+    Dyn: ?Sized,
+{
+    repr: Repr<Dyn, N, H>,
+}
+
+impl<Dyn, const N: usize, const H: usize> Default for SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<Dyn, const N: usize, const H: usize> Send for SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+    Dyn: Send,
+{
+}
+
+unsafe impl<Dyn, const N: usize, const H: usize> Sync for SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+impl<Dyn, const N: usize, const H: usize> SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    #[must_use]
+    /// Creates a new empty [`SmallFuseBox<Dyn, N, H>`].
+    pub fn new() -> Self {
+        Self {
+            repr: Repr::Inline(ArrayFuseBox::new()),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the length of this [`SmallFuseBox<Dyn, N, H>`] in items.
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Inline(inline) => inline.len(),
+            Repr::Heap(heap) => heap.len(),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns `true` if values have been moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.repr, Repr::Heap(_))
+    }
+
+    #[cfg(feature = "nightly")]
+    #[inline]
+    /// Appends an element to the vector, moving everything to the heap if it does not fit.
+    pub fn push<T>(&mut self, v: T)
+    where
+        T: 'static,
+        T: Unsize<Dyn>,
+        Dyn: 'static,
+    {
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce(v, |p| p) }
+    }
+
+    #[inline]
+    /// Appends an element to the vector using `coerce` to unsize it,
+    /// moving everything to the heap if it does not fit.
+    ///
+    /// See [`FuseBox::push_coerce`].
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce<T>(&mut self, v: T, coerce: fn(*mut T) -> *mut Dyn)
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        let inline = match &mut self.repr {
+            Repr::Heap(heap) => return unsafe { heap.push_coerce(v, coerce) },
+            Repr::Inline(inline) => inline,
+        };
+        if let Err(v) = unsafe { inline.push_coerce(v, coerce) } {
+            let mut heap = spill(inline);
+            unsafe { heap.push_coerce(v, coerce) };
+            self.repr = Repr::Heap(heap);
+        }
+    }
+
+    #[inline]
+    /// Retrieves `&mut Dyn` from [`SmallFuseBox`].
+    pub fn get_mut(&mut self, n: usize) -> Option<&mut Dyn> {
+        match &mut self.repr {
+            Repr::Inline(inline) => inline.get_mut(n),
+            Repr::Heap(heap) => heap.get_mut(n),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Retrieves `&Dyn` from [`SmallFuseBox`].
+    pub fn get(&self, n: usize) -> Option<&Dyn> {
+        match &self.repr {
+            Repr::Inline(inline) => inline.get(n),
+            Repr::Heap(heap) => heap.get(n),
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&Dyn` stored in this [`SmallFuseBox`]
+    pub fn iter(&'_ self) -> Iter<'_, Dyn> {
+        match &self.repr {
+            Repr::Inline(inline) => inline.iter(),
+            Repr::Heap(heap) => heap.iter(),
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&mut Dyn` stored in this [`SmallFuseBox`].
+    pub fn iter_mut(&'_ mut self) -> IterMut<'_, Dyn> {
+        match &mut self.repr {
+            Repr::Inline(inline) => inline.iter_mut(),
+            Repr::Heap(heap) => heap.iter_mut(),
+        }
+    }
+}
+
+impl<Dyn, const N: usize, const H: usize> Index<usize> for SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<Dyn, const N: usize, const H: usize> IndexMut<usize> for SmallFuseBox<Dyn, N, H>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
@@ -0,0 +1,324 @@
+use alloc::{
+    alloc::{alloc, dealloc, realloc},
+    vec::Vec,
+};
+#[cfg(feature = "nightly")]
+use core::marker::Unsize;
+use core::{
+    alloc::Layout,
+    marker::PhantomData,
+    mem::{align_of, size_of},
+    ops::{Index, IndexMut},
+    ptr::{self, drop_in_place, NonNull},
+    slice,
+};
+
+use super::meta::{self, Metadata};
+
+/// Contigous type-erased append-only vector with fixed-size slots
+///
+/// Every element occupies exactly `SLOT` bytes and lives at `index * SLOT`,
+/// so only metadata is stored per element. Pushing a type that does not fit
+/// into a slot, or whose alignment does not divide `SLOT`, fails to compile.
+///
+/// `Dyn` shall be `dyn Trait`
+///
+/// ```compile_fail
+/// # use std::fmt::Debug;
+/// # use fusebox::{fuse_push, StrideFuseBox};
+/// let mut fb = StrideFuseBox::<dyn Debug, 8>::new();
+/// fuse_push!(fb, 0u128);
+/// ```
+///
+/// With `SLOT == 0` slots are only aligned to 1, so over-aligned zero-sized types fail too.
+///
+/// ```compile_fail
+/// # use std::fmt::Debug;
+/// # use fusebox::{fuse_push, StrideFuseBox};
+/// #[derive(Debug)]
+/// #[repr(align(8))]
+/// struct Z8;
+/// let mut fb = StrideFuseBox::<dyn Debug, 0>::new();
+/// fuse_push!(fb, Z8);
+/// ```
+pub struct StrideFuseBox<Dyn, const SLOT: usize>
+where
+    Dyn: ?Sized,
+{
+    metas: Vec<Metadata<Dyn>>,
+    inner: NonNull<u8>,
+    cap: usize,
+}
+
+impl<Dyn, const SLOT: usize> Default for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Dyn, const SLOT: usize> Drop for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+{
+    fn drop(&mut self) {
+        // Safety:
+        // every slot below len holds a valid value
+        unsafe {
+            for val in self.iter_mut() {
+                drop_in_place(val);
+            }
+            if self.cap != 0 {
+                dealloc(self.inner.as_ptr(), Self::layout(self.cap));
+            }
+        }
+    }
+}
+
+unsafe impl<Dyn, const SLOT: usize> Send for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+    Dyn: Send,
+{
+}
+
+unsafe impl<Dyn, const SLOT: usize> Sync for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+    Dyn: Sync,
+{
+}
+
+impl<Dyn, const SLOT: usize> StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+{
+    /// Alignment of every slot: the largest power of two dividing `SLOT`.
+    const ALIGN: usize = if SLOT == 0 {
+        1
+    } else {
+        1 << SLOT.trailing_zeros()
+    };
+
+    #[must_use]
+    /// Creates a new [`StrideFuseBox<Dyn, SLOT>`].
+    pub fn new() -> Self {
+        Self {
+            metas: Vec::new(),
+            // Safety: ALIGN is a non-zero power of two
+            inner: unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(Self::ALIGN)) },
+            cap: 0,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    /// Returns the length of this [`StrideFuseBox<Dyn, SLOT>`] in items.
+    pub fn len(&self) -> usize {
+        self.metas.len()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn layout(cap: usize) -> Layout {
+        // Safety: ALIGN is a power of two and callers never exceed isize::MAX
+        unsafe { Layout::from_size_align_unchecked(cap * SLOT, Self::ALIGN) }
+    }
+
+    #[cold]
+    fn grow(&mut self) {
+        let cap = if self.cap == 0 {
+            4
+        } else {
+            self.cap
+                .checked_mul(2)
+                .expect("New capacity overflowed usize")
+        };
+        cap.checked_mul(SLOT)
+            .filter(|&size| size <= isize::MAX as usize)
+            .expect("New capacity overflowed isize");
+        unsafe {
+            let new = if self.cap == 0 {
+                alloc(Self::layout(cap))
+            } else {
+                realloc(self.inner.as_ptr(), Self::layout(self.cap), cap * SLOT)
+            };
+            if new.is_null() {
+                panic!(
+                    "Failed to allocate memory for {}",
+                    core::any::type_name::<Self>()
+                )
+            }
+            self.inner = NonNull::new_unchecked(new);
+        }
+        self.cap = cap;
+    }
+
+    #[cfg(feature = "nightly")]
+    #[inline]
+    /// Appends an element to the vector.
+    pub fn push<T>(&mut self, v: T)
+    where
+        T: 'static,
+        T: Unsize<Dyn>,
+        Dyn: 'static,
+    {
+        // Safety: the closure is the unsizing coercion
+        unsafe { self.push_coerce(v, |p| p) }
+    }
+
+    #[inline]
+    /// Appends an element to the vector, using `coerce` to unsize it.
+    ///
+    /// See [`FuseBox::push_coerce`](crate::FuseBox::push_coerce).
+    ///
+    /// # Safety
+    /// `coerce` must return its argument unsized to `Dyn`, as `|p| p` does.
+    pub unsafe fn push_coerce<T>(&mut self, v: T, coerce: fn(*mut T) -> *mut Dyn)
+    where
+        T: 'static,
+        Dyn: 'static,
+    {
+        const {
+            assert!(size_of::<T>() <= SLOT, "value does not fit into a slot");
+            assert!(
+                align_of::<T>() <= Self::ALIGN,
+                "value alignment does not divide the slot size"
+            );
+        }
+        let meta = unsafe { meta::from_coerce(coerce) };
+
+        if SLOT != 0 && self.len() == self.cap {
+            self.grow();
+        }
+        // Safety: slot is in-bounds and aligned for T
+        unsafe {
+            self.inner
+                .as_ptr()
+                .add(self.len() * SLOT)
+                .cast::<T>()
+                .write(v)
+        }
+        self.metas.push(meta);
+    }
+
+    #[inline]
+    unsafe fn get_raw(&self, n: usize) -> *mut Dyn {
+        let meta = self.metas[n];
+        unsafe { meta::from_raw_parts_mut(self.inner.as_ptr().add(n * SLOT), meta) }
+    }
+
+    #[inline]
+    /// Retrieves `&mut Dyn` from [`StrideFuseBox`].
+    pub fn get_mut(&mut self, n: usize) -> Option<&mut Dyn> {
+        if self.len() <= n {
+            return None;
+        }
+        unsafe { Some(&mut *self.get_raw(n)) }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Retrieves `&Dyn` from [`StrideFuseBox`].
+    pub fn get(&self, n: usize) -> Option<&Dyn> {
+        if self.len() <= n {
+            return None;
+        }
+        unsafe { Some(&*self.get_raw(n)) }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&Dyn` stored in this [`StrideFuseBox`]
+    pub fn iter(&'_ self) -> StrideIter<'_, Dyn, SLOT> {
+        StrideIter {
+            metas: self.metas.iter(),
+            data_ptr: self.inner,
+            _tag: PhantomData,
+        }
+    }
+
+    #[must_use]
+    /// Returns an iterator over `&mut Dyn` stored in this [`StrideFuseBox`].
+    pub fn iter_mut(&'_ mut self) -> StrideIterMut<'_, Dyn, SLOT> {
+        StrideIterMut {
+            metas: self.metas.iter(),
+            data_ptr: self.inner,
+            _tag: PhantomData,
+        }
+    }
+}
+
+impl<Dyn, const SLOT: usize> Index<usize> for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+{
+    type Output = Dyn;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len());
+        unsafe { &*self.get_raw(index) }
+    }
+}
+
+impl<Dyn, const SLOT: usize> IndexMut<usize> for StrideFuseBox<Dyn, SLOT>
+where
+    Dyn: ?Sized,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        assert!(index < self.len());
+        unsafe { &mut *self.get_raw(index) }
+    }
+}
+
+macro_rules! impl_stride_iter {
+    ($iter:tt $(, $mut:tt)?) => {
+        pub struct $iter<'f, Dyn, const SLOT: usize>
+        where
+            Dyn: ?Sized,
+        {
+            metas: slice::Iter<'f, Metadata<Dyn>>,
+            data_ptr: NonNull<u8>,
+            _tag: PhantomData<&'f $($mut)? StrideFuseBox<Dyn, SLOT>>,
+        }
+
+        impl<'f, Dyn, const SLOT: usize> Iterator for $iter<'f, Dyn, SLOT>
+        where
+            Dyn: ?Sized,
+        {
+            type Item = &'f $($mut)? Dyn;
+
+            #[inline]
+            fn next(&mut self) -> Option<Self::Item> {
+                let meta = *self.metas.next()?;
+                let ptr = self.data_ptr.as_ptr();
+                unsafe {
+                    self.data_ptr = NonNull::new_unchecked(ptr.add(SLOT));
+                    Some(&$($mut)? *meta::from_raw_parts_mut(ptr, meta))
+                }
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.metas.size_hint()
+            }
+        }
+
+        impl<'f, Dyn, const SLOT: usize> ExactSizeIterator for $iter<'f, Dyn, SLOT>
+        where
+            Dyn: ?Sized,
+        {
+        }
+    };
+}
+
+impl_stride_iter!(StrideIter);
+impl_stride_iter!(StrideIterMut, mut);
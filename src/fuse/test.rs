@@ -1,6 +1,19 @@
-use super::FuseBox;
-use std::{fmt::Debug, ops::ShlAssign};
+use super::{
+    array::ArrayFuseBox, slab::FuseSlab, small::SmallFuseBox, stride::StrideFuseBox, FuseBox,
+};
+use crate::fuse_push;
+use std::{cell::Cell, fmt::Debug, ops::ShlAssign, rc::Rc};
+
+#[derive(Debug)]
+struct Counted(Rc<Cell<usize>>);
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1)
+    }
+}
+
 #[test]
+#[cfg(feature = "nightly")]
 fn test() {
     let mut fb = FuseBox::<dyn Debug>::default();
 
@@ -22,6 +35,28 @@ fn test() {
 }
 
 #[test]
+fn test_stable() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+
+    let v = 16u64;
+    fuse_push!(fb, v);
+
+    let v = 1u8;
+    fuse_push!(fb, v);
+
+    let v = 2u8;
+    fuse_push!(fb, v);
+
+    let v = [1u8; 5];
+    fuse_push!(fb, v);
+
+    for v in fb.iter() {
+        println!("{v:?}")
+    }
+}
+
+#[test]
+#[cfg(feature = "nightly")]
 fn silly() {
     let mut fb = FuseBox::<[u8]>::default();
 
@@ -40,6 +75,25 @@ fn silly() {
 }
 
 #[test]
+fn silly_stable() {
+    let mut fb = FuseBox::<[u8]>::default();
+
+    let v = [0; 2];
+    fuse_push!(fb, v);
+    let v = [0; 4];
+    fuse_push!(fb, v);
+    let v = [0; 8];
+    fuse_push!(fb, v);
+    let v = [0; 16];
+    fuse_push!(fb, v);
+
+    for v in fb.iter() {
+        println!("{v:?}")
+    }
+}
+
+#[test]
+#[cfg(feature = "nightly")]
 // https://github.com/JohnDowson/fusebox/issues/4
 fn issue4() {
     let mut fb = FuseBox::<dyn Debug>::default();
@@ -52,8 +106,21 @@ fn issue4() {
     }
 }
 
+#[test]
+fn issue4_stable() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+
+    fuse_push!(fb, 42u8);
+    fuse_push!(fb, 1337_u128);
+
+    for v in fb.iter() {
+        println!("{v:?}")
+    }
+}
+
 #[allow(clippy::all)]
 #[test]
+#[cfg(feature = "nightly")]
 // https://github.com/JohnDowson/fusebox/issues/5
 fn issue5() {
     let mut x: FuseBox<dyn Debug> = FuseBox::new();
@@ -70,7 +137,25 @@ fn issue5() {
     x.push(0_u32);
 }
 
+#[allow(clippy::all)]
 #[test]
+fn issue5_stable() {
+    let mut x: FuseBox<dyn Debug> = FuseBox::new();
+    fuse_push!(x, 0_u8);
+    fuse_push!(x, 0_u8);
+
+    fuse_push!(x, 0_u16);
+    fuse_push!(x, 0_u8);
+    fuse_push!(x, 0_u8);
+    fuse_push!(x, 0_u8);
+    fuse_push!(x, 0_u8);
+
+    fuse_push!(x, 0_u16);
+    fuse_push!(x, 0_u32);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
 fn mutate() {
     trait ShlDebug: ShlAssign<u8> + Debug {}
     impl<T> ShlDebug for T where T: ShlAssign<u8> + Debug {}
@@ -99,3 +184,1053 @@ fn mutate() {
         println!("{v:?}")
     }
 }
+
+#[test]
+fn mutate_stable() {
+    trait ShlDebug: ShlAssign<u8> + Debug {}
+    impl<T> ShlDebug for T where T: ShlAssign<u8> + Debug {}
+    let mut fb = FuseBox::<dyn ShlDebug>::default();
+
+    let v = 16u64;
+    fuse_push!(fb, v);
+
+    let v = 1u8;
+    fuse_push!(fb, v);
+
+    let v = 2u8;
+    fuse_push!(fb, v);
+
+    let v = 5u32;
+    fuse_push!(fb, v);
+
+    for v in fb.iter() {
+        println!("{v:?}")
+    }
+    println!();
+    for v in fb.iter_mut() {
+        v.shl_assign(1);
+    }
+    for v in fb.iter() {
+        println!("{v:?}")
+    }
+}
+
+#[test]
+fn sort_by_type() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, 3u8);
+    fuse_push!(fb, [4u16; 3]);
+    fuse_push!(fb, 5u64);
+    fuse_push!(fb, 6u8);
+
+    // Types may not share one vtable, so only grouping by metadata is checked
+    let tagged = |fb: &FuseBox<dyn Debug>| -> Vec<_> {
+        fb.headers
+            .iter()
+            .map(|h| h.meta)
+            .zip(fb.iter().map(|v| format!("{v:?}")))
+            .collect()
+    };
+    let before = tagged(&fb);
+    fb.sort_by_type();
+
+    let mut metas = Vec::new();
+    for (meta, _) in &before {
+        if !metas.contains(meta) {
+            metas.push(*meta);
+        }
+    }
+    let expected: Vec<_> = metas
+        .iter()
+        .flat_map(|m| before.iter().filter(move |(meta, _)| meta == m))
+        .cloned()
+        .collect();
+    assert_eq!(tagged(&fb), expected);
+
+    let mut runs = Vec::new();
+    fb.for_each_group(|meta, group| runs.push((meta, group.len())));
+    assert_eq!(
+        runs.iter().map(|&(meta, _)| meta).collect::<Vec<_>>(),
+        metas
+    );
+    let mut start = 0;
+    for (meta, len) in runs {
+        assert!(fb.headers[start..start + len]
+            .iter()
+            .all(|h| h.meta == meta));
+        start += len;
+    }
+    assert_eq!(start, fb.len());
+}
+
+#[test]
+fn for_each_group_mut() {
+    trait ShlDebug: ShlAssign<u8> + Debug {}
+    impl<T> ShlDebug for T where T: ShlAssign<u8> + Debug {}
+    let mut fb = FuseBox::<dyn ShlDebug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 1u32);
+    fuse_push!(fb, 1u8);
+
+    fb.sort_by_type();
+    fb.for_each_group_mut(|_, group| {
+        for v in group {
+            v.shl_assign(1);
+        }
+    });
+
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["2", "2", "2"]);
+}
+
+#[test]
+fn stride() {
+    let mut fb = StrideFuseBox::<dyn Debug, 16>::new();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u128);
+    fuse_push!(fb, [3u16; 4]);
+    fuse_push!(fb, ());
+    fuse_push!(fb, 5u32);
+
+    assert_eq!(fb.len(), 5);
+    assert_eq!(format!("{:?}", &fb[2]), "[3, 3, 3, 3]");
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "2", "[3, 3, 3, 3]", "()", "5"]);
+}
+
+#[test]
+fn small() {
+    let mut fb = SmallFuseBox::<dyn Debug, 16, 4>::new();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u32);
+    fuse_push!(fb, [3u8; 4]);
+    assert!(!fb.spilled());
+    assert_eq!(format!("{:?}", &fb[1]), "2");
+
+    fuse_push!(fb, 4u64);
+    assert!(fb.spilled());
+    fuse_push!(fb, 5u16);
+
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "2", "[3, 3, 3, 3]", "4", "5"]);
+}
+
+#[test]
+fn zero_sized() {
+    #[derive(Debug)]
+    #[repr(align(8))]
+    struct Aligned;
+
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::new();
+    fuse_push!(fb, Aligned);
+    fuse_push!(fb, ());
+    assert_eq!(
+        fb.get(0)
+            .map(|v| v as *const dyn Debug as *const u8 as usize % 8),
+        Some(0)
+    );
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 1u8);
+    drop(fb);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn zero_sized_only() {
+    thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Debug)]
+    #[repr(align(16))]
+    struct Silent;
+    impl Drop for Silent {
+        fn drop(&mut self) {
+            DROPS.set(DROPS.get() + 1)
+        }
+    }
+
+    // Never allocates, yet values are still aligned and dropped
+    let mut fb = FuseBox::<dyn Debug>::new();
+    fuse_push!(fb, Silent);
+    fuse_push!(fb, ());
+    fuse_push!(fb, Silent);
+    for v in fb.iter() {
+        assert_eq!(v as *const dyn Debug as *const u8 as usize % 16, 0);
+    }
+    drop(fb);
+    assert_eq!(DROPS.get(), 2);
+}
+
+#[test]
+fn array() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = ArrayFuseBox::<dyn Debug, 24, 3>::new();
+    assert!(fuse_push!(fb, 1u8).is_ok());
+    assert!(fuse_push!(fb, Counted(drops.clone())).is_ok());
+    assert_eq!(fuse_push!(fb, [0u8; 9]).unwrap_err(), [0u8; 9]);
+    assert!(fuse_push!(fb, 2u16).is_ok());
+    assert_eq!(fuse_push!(fb, 3u8).unwrap_err(), 3);
+    assert_eq!(fb.len(), 3);
+    assert_eq!(format!("{:?}", &fb[2]), "2");
+
+    drop(fb);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn fuse_push() {
+    let mut fb = FuseBox::<dyn Debug>::new();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, [2u32; 2]);
+    let mut small = SmallFuseBox::<dyn Debug, 8>::new();
+    fuse_push!(small, 3u64);
+    let mut array = ArrayFuseBox::<dyn Debug, 8>::new();
+    assert!(fuse_push!(array, 4u64).is_ok());
+    assert!(fuse_push!(array, 5u8).is_err());
+
+    let printed: Vec<_> = fb
+        .iter()
+        .chain(small.iter())
+        .chain(array.iter())
+        .map(|v| format!("{v:?}"))
+        .collect();
+    assert_eq!(printed, ["1", "[2, 2]", "3", "4"]);
+}
+
+#[test]
+fn double_ended() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+    for v in 0u8..6 {
+        fuse_push!(fb, v);
+    }
+
+    let printed: Vec<_> = fb.iter().rev().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["5", "4", "3", "2", "1", "0"]);
+
+    let mut iter = fb.iter_mut();
+    assert_eq!(format!("{:?}", iter.nth(1).unwrap()), "1");
+    assert_eq!(format!("{:?}", iter.nth_back(1).unwrap()), "4");
+    assert_eq!(format!("{:?}", iter.next_back().unwrap()), "3");
+    assert_eq!(format!("{:?}", iter.next().unwrap()), "2");
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+}
+
+#[test]
+fn into_iter() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    for n in 0..4 {
+        fuse_push!(fb, Counted(drops.clone()));
+        fuse_push!(fb, n as u8);
+    }
+    fuse_push!(fb, ());
+
+    let mut iter = fb.into_iter();
+    assert_eq!(iter.len(), 9);
+    let first = iter.next().unwrap();
+    assert_eq!(format!("{:?}", iter.next().unwrap()), "0");
+    assert_eq!(format!("{:?}", iter.next_back().unwrap()), "()");
+    assert_eq!(drops.get(), 0);
+
+    drop(iter);
+    assert_eq!(drops.get(), 3);
+    drop(first);
+    assert_eq!(drops.get(), 4);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn collection_traits() {
+    fn sum_debug<'a>(values: impl IntoIterator<Item = &'a (dyn Debug + 'static)>) -> String {
+        values.into_iter().map(|v| format!("{v:?}")).collect()
+    }
+
+    let mut fb: FuseBox<dyn Debug> = (0u8..3).collect();
+    fb.extend([3u16, 4]);
+    assert_eq!(sum_debug(&fb), "01234");
+
+    let drops = Rc::new(Cell::new(0));
+    let boxed: Vec<Box<dyn Debug>> = vec![
+        Box::new(5u64),
+        Box::new(()),
+        Box::new(Counted(drops.clone())),
+    ];
+    fb.extend_boxed(boxed);
+    assert_eq!(fb.len(), 8);
+    assert_eq!(drops.get(), 0);
+
+    let mut n = 0;
+    for v in &mut fb {
+        n += format!("{v:?}").len();
+    }
+    assert_eq!(n, 5 + 1 + 2 + "Counted(Cell { value: 0 })".len());
+    drop(fb);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn push_box() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fb.push_box(Box::new(Counted(drops.clone())));
+    fb.push_box(Box::new(2u128));
+    fb.push_box(Box::new(()));
+    assert_eq!(drops.get(), 0);
+
+    assert_eq!(format!("{:?}", &fb[2]), "2");
+    assert_eq!(
+        fb.get(2)
+            .map(|v| v as *const dyn Debug as *const u8 as usize % 16),
+        Some(0)
+    );
+    drop(fb);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn boxed_vec() {
+    let drops = Rc::new(Cell::new(0));
+    let boxed: Vec<Box<dyn Debug>> = vec![
+        Box::new(1u8),
+        Box::new(Counted(drops.clone())),
+        Box::new(2u128),
+        Box::new(()),
+        Box::new([3u16; 3]),
+    ];
+    let fb = FuseBox::from(boxed);
+    assert_eq!(fb.bytes.cap_bytes, 48);
+    assert_eq!(fb.len(), 5);
+
+    let boxed = fb.into_boxed_vec();
+    assert_eq!(drops.get(), 0);
+    let printed: Vec<_> = boxed.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed[0], "1");
+    assert_eq!(printed[2..], ["2", "()", "[3, 3, 3]"]);
+    drop(boxed);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn take() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 2u128);
+    fuse_push!(fb, 3u16);
+
+    let taken = fb.take(1);
+    assert_eq!(fb.len(), 3);
+    assert_eq!(drops.get(), 0);
+    drop(taken);
+    assert_eq!(drops.get(), 1);
+
+    // u128 moved down to the first 16-byte boundary
+    assert_eq!(fb.headers[1].offset, 16);
+    assert_eq!(fb.bytes.len_bytes, 34);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "2", "3"]);
+
+    assert_eq!(format!("{:?}", fb.take_last().unwrap()), "3");
+    assert_eq!(format!("{:?}", fb.take(0)), "1");
+    fuse_push!(fb, 4u8);
+    assert_eq!(fb.headers[1].offset, 16);
+    assert_eq!(format!("{:?}", fb.take(0)), "2");
+    assert_eq!(fb.headers[0].offset, 0);
+    assert_eq!(format!("{:?}", fb.take_last().unwrap()), "4");
+    assert!(fb.take_last().is_none());
+}
+
+#[test]
+fn pop_with() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 7u32);
+
+    assert_eq!(fb.pop_with(|v| format!("{v:?}")).as_deref(), Some("7"));
+    let len = fb.pop_with(|v| {
+        assert_eq!(drops.get(), 0);
+        format!("{v:?}").len()
+    });
+    assert_eq!(len, Some("Counted(Cell { value: 0 })".len()));
+    assert_eq!(drops.get(), 1);
+    assert_eq!(fb.pop_with(|_| ()), None);
+
+    fuse_push!(fb, Counted(drops.clone()));
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fb.pop_with(|_| panic!("boom"));
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(drops.get(), 2);
+    assert!(fb.is_empty());
+    fuse_push!(fb, 5u8);
+    assert_eq!(fb.headers[0].offset, 0);
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn replace() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 1u32);
+    fuse_push!(fb, 2u8);
+
+    // u16 fits the u32 slot
+    assert!(!fb.replace(1, 3u16));
+    assert_eq!(fb.headers[2].offset, 12);
+    // [u8; 6] does not, the u8 after it moves up
+    assert!(fb.replace(1, [4u8; 6]));
+    assert_eq!(fb.headers[2].offset, 14);
+    assert_eq!(drops.get(), 0);
+
+    assert!(!fb.replace(0, ()));
+    assert_eq!(drops.get(), 1);
+    assert!(!fb.replace(2, 5u64));
+    assert_eq!(fb.headers[2].offset, 16);
+    assert!(fb.replace(2, 6u128));
+
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["()", "[4, 4, 4, 4, 4, 4]", "6"]);
+    assert_eq!(
+        fb.get(2)
+            .map(|v| v as *const dyn Debug as *const u8 as usize % 16),
+        Some(0)
+    );
+
+    fuse_push!(fb, Counted(drops.clone()));
+    assert_eq!(format!("{:?}", fb.take(0)), "()");
+    assert!(!fb.replace(2, 7u8));
+    drop(fb);
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn swap_rotate() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, [3u16; 3]);
+    fuse_push!(fb, ());
+    let printed =
+        |fb: &FuseBox<dyn Debug>| -> Vec<String> { fb.iter().map(|v| format!("{v:?}")).collect() };
+    let counted = "Counted(Cell { value: 0 })";
+
+    fb.swap(0, 1);
+    assert_eq!(fb.headers[1].offset, 8);
+    fb.swap(3, 1);
+    fb.swap(2, 2);
+    assert_eq!(printed(&fb), ["2", "[3, 3, 3]", counted, "1", "()"]);
+    // Counted is re-aligned after the [u16; 3] and the rest shifted up
+    assert_eq!(fb.headers[2].offset, 16);
+    assert_eq!(fb.headers[3].offset, 16 + size_of::<Counted>());
+
+    fb.rotate_left(2);
+    assert_eq!(printed(&fb), [counted, "1", "()", "2", "[3, 3, 3]"]);
+    assert_eq!(fb.bytes.len_bytes, size_of::<Counted>() + 16 + 6);
+    fb.rotate_right(1);
+    fb.rotate_right(0);
+    fb.rotate_left(5);
+    assert_eq!(printed(&fb), ["[3, 3, 3]", counted, "1", "()", "2"]);
+    for v in &fb {
+        let align = align_of_val(v);
+        assert_eq!(v as *const dyn Debug as *const u8 as usize % align, 0);
+    }
+
+    assert_eq!(drops.get(), 0);
+    drop(fb);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn sort_by() {
+    trait Voice: Debug {
+        fn priority(&self) -> u8;
+    }
+    #[derive(Debug)]
+    struct Sine(u64);
+    #[derive(Debug)]
+    struct Noise(u8);
+    impl Voice for Sine {
+        fn priority(&self) -> u8 {
+            self.0 as u8
+        }
+    }
+    impl Voice for Noise {
+        fn priority(&self) -> u8 {
+            self.0
+        }
+    }
+
+    let mut fb = FuseBox::<dyn Voice>::default();
+    fuse_push!(fb, Noise(3));
+    fuse_push!(fb, Sine(1));
+    fuse_push!(fb, Noise(2));
+    fuse_push!(fb, Sine(3));
+    fuse_push!(fb, Noise(1));
+
+    fb.sort_by_key(|v| v.priority());
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(
+        printed,
+        ["Sine(1)", "Noise(1)", "Noise(2)", "Noise(3)", "Sine(3)"]
+    );
+    assert_eq!(fb.headers[1].offset, 8);
+    assert_eq!(fb.headers[4].offset, 16);
+    assert_eq!(fb.bytes.len_bytes, 24);
+
+    fb.sort_by(|a, b| b.priority().cmp(&a.priority()));
+    let priorities: Vec<_> = fb.iter().map(|v| v.priority()).collect();
+    assert_eq!(priorities, [3, 3, 2, 1, 1]);
+    assert_eq!(format!("{:?}", &fb[0]), "Noise(3)");
+}
+
+#[test]
+fn retain() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, 3u8);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 4u32);
+
+    fb.retain(|v| !format!("{v:?}").starts_with("Counted"));
+    assert_eq!(drops.get(), 2);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "2", "3", "4"]);
+    assert_eq!(fb.headers[1].offset, 8);
+    assert_eq!(fb.headers[3].offset, 20);
+    assert_eq!(fb.bytes.len_bytes, 24);
+
+    let mut n = 0;
+    fb.retain_mut(|_| {
+        n += 1;
+        n % 2 == 0
+    });
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["2", "4"]);
+    assert_eq!(fb.headers[1].offset, 8);
+    fuse_push!(fb, 5u8);
+    assert_eq!(fb.headers[2].offset, 12);
+}
+
+#[test]
+fn retain_panic() {
+    #[derive(Debug)]
+    struct Bomb(Rc<Cell<usize>>);
+    impl Drop for Bomb {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+            panic!("boom");
+        }
+    }
+
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 2u16);
+    fuse_push!(fb, 3u64);
+    fuse_push!(fb, Counted(drops.clone()));
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fb.retain(|v| match format!("{v:?}").as_str() {
+            "3" => panic!("predicate"),
+            s => !s.starts_with("Counted"),
+        });
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(drops.get(), 1);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed[..3], ["1", "2", "3"]);
+    assert_eq!(fb.headers[2].offset, 8);
+
+    fuse_push!(fb, Bomb(drops.clone()));
+    fuse_push!(fb, 4u8);
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fb.retain(|v| format!("{v:?}").len() == 1);
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(drops.get(), 3);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "2", "3", "4"]);
+    drop(fb);
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn drain() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 3u8);
+    fuse_push!(fb, Counted(drops.clone()));
+    fuse_push!(fb, 4u32);
+
+    let drained: Vec<_> = fb.drain(1..3).map(|v| format!("{v:?}")).collect();
+    assert_eq!(drained, ["2", "Counted(Cell { value: 0 })"]);
+    assert_eq!(drops.get(), 1);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(printed, ["1", "3", "Counted(Cell { value: 1 })", "4"]);
+    assert_eq!(fb.headers[2].offset, 8);
+
+    // Unconsumed elements are dropped with the iterator
+    let mut drain = fb.drain(2..);
+    assert_eq!(drain.len(), 2);
+    assert_eq!(
+        format!("{:?}", drain.next().unwrap()),
+        "Counted(Cell { value: 1 })"
+    );
+    drop(drain);
+    assert_eq!(drops.get(), 2);
+    assert_eq!(fb.len(), 2);
+    assert_eq!(fb.bytes.len_bytes, 2);
+
+    // Leaking keeps the elements before the range
+    std::mem::forget(fb.drain(1..=1));
+    assert_eq!(fb.len(), 1);
+    fb.drain(..).for_each(drop);
+    assert!(fb.is_empty());
+}
+
+#[test]
+fn extract_if() {
+    let drops = Rc::new(Cell::new(0));
+    let mut fb = FuseBox::<dyn Debug>::default();
+    for n in 0..6u16 {
+        fuse_push!(fb, n);
+        fuse_push!(fb, Counted(drops.clone()));
+    }
+
+    let mut odd = 0;
+    let extracted: Vec<_> = fb
+        .extract_if(|v| {
+            let s = format!("{v:?}");
+            s.len() == 1 && s.parse::<u16>().unwrap() % 2 == 1 && {
+                odd += 1;
+                true
+            }
+        })
+        .collect();
+    assert_eq!(odd, 3);
+    assert_eq!(extracted.len(), 3);
+    assert_eq!(fb.len(), 9);
+
+    // Stops early, the rest is kept
+    let first = fb
+        .extract_if(|v| format!("{v:?}").starts_with("Counted"))
+        .next();
+    assert!(first.is_some());
+    drop(first);
+    assert_eq!(drops.get(), 1);
+    assert_eq!(fb.len(), 8);
+    let printed: Vec<_> = fb.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!((&*printed[0], &*printed[2]), ("0", "2"));
+    drop(fb);
+    assert_eq!(drops.get(), 6);
+}
+
+#[test]
+fn append_split_transfer() {
+    let drops = Rc::new(Cell::new(0));
+    let printed =
+        |fb: &FuseBox<dyn Debug>| -> Vec<String> { fb.iter().map(|v| format!("{v:?}")).collect() };
+    let mut a = FuseBox::<dyn Debug>::default();
+    fuse_push!(a, 1u8);
+    fuse_push!(a, Counted(drops.clone()));
+    let mut b = FuseBox::<dyn Debug>::default();
+    fuse_push!(b, 2u8);
+    fuse_push!(b, 3u128);
+    fuse_push!(b, ());
+
+    a.append(&mut b);
+    assert!(b.is_empty());
+    assert_eq!(b.bytes.len_bytes, 0);
+    assert_eq!(a.bytes.cap_bytes, 48);
+    assert_eq!(printed(&a)[2..], ["2", "3", "()"]);
+    assert_eq!(a.headers[3].offset, 32);
+    assert_eq!(
+        a.get(3)
+            .map(|v| v as *const dyn Debug as *const u8 as usize % 16),
+        Some(0)
+    );
+
+    let mut tail = a.split_off(2);
+    assert_eq!(printed(&a), ["1", "Counted(Cell { value: 0 })"]);
+    assert_eq!(printed(&tail), ["2", "3", "()"]);
+    assert_eq!(a.bytes.len_bytes, 16);
+    assert_eq!(tail.headers[1].offset, 16);
+    assert!(a.split_off(2).is_empty());
+
+    a.transfer(1, &mut tail);
+    assert_eq!(drops.get(), 0);
+    assert_eq!(printed(&a), ["1"]);
+    assert_eq!(a.bytes.len_bytes, 1);
+    assert_eq!(printed(&tail)[3], "Counted(Cell { value: 0 })");
+    tail.transfer(0, &mut a);
+    assert_eq!(printed(&a), ["1", "2"]);
+    assert_eq!(tail.headers[0].offset, 0);
+    drop(a);
+    assert_eq!(drops.get(), 0);
+    drop(tail);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn slices() {
+    let mut fb = FuseBox::<dyn ShlAssign<u8>>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, 3u16);
+    fuse_push!(fb, 4u32);
+
+    let mut slice = fb.slice_mut(1..);
+    assert_eq!(slice.len(), 3);
+    *slice.first_mut().unwrap() <<= 1;
+    slice[1] <<= 2;
+    let mut inner = slice.slice_mut(2..=2);
+    *inner.last_mut().unwrap() <<= 3;
+    assert!(slice.slice_mut(3..).is_empty());
+    for v in slice.slice_mut(..1).iter_mut() {
+        *v <<= 1;
+    }
+    for v in fb.as_mut_slice() {
+        *v <<= 1;
+    }
+
+    let mut fb = FuseBox::<dyn Debug>::default();
+    fuse_push!(fb, 1u8);
+    fuse_push!(fb, 2u64);
+    fuse_push!(fb, "three");
+    fuse_push!(fb, ());
+    let all = fb.as_slice();
+    let mid = all.slice(1..3);
+    assert_eq!(mid.len(), 2);
+    assert_eq!(format!("{:?}", mid.first().unwrap()), "2");
+    assert_eq!(format!("{:?}", &mid[1]), "\"three\"");
+    assert!(mid.get(2).is_none());
+    assert_eq!(mid.slice(1..).iter().count(), 1);
+    assert_eq!(all.into_iter().len(), 4);
+    assert!(fb.slice(4..).last().is_none());
+}
+
+#[test]
+#[should_panic = "range end 5 out of bounds for length 4"]
+fn slice_out_of_bounds() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+    for v in [1u8; 4] {
+        fuse_push!(fb, v);
+    }
+    let _ = fb.slice(2..5);
+}
+
+#[test]
+fn disjoint_mut() {
+    use core::slice::GetDisjointMutError;
+
+    trait Node: Debug {
+        fn set(&mut self, v: u32);
+        fn value(&self) -> u32;
+    }
+    #[derive(Debug)]
+    struct Small(u8);
+    #[derive(Debug)]
+    struct Big(u64);
+    impl Node for Small {
+        fn set(&mut self, v: u32) {
+            self.0 = v as u8;
+        }
+        fn value(&self) -> u32 {
+            self.0.into()
+        }
+    }
+    impl Node for Big {
+        fn set(&mut self, v: u32) {
+            self.0 = v.into();
+        }
+        fn value(&self) -> u32 {
+            self.0 as u32
+        }
+    }
+
+    let mut fb = FuseBox::<dyn Node>::default();
+    fuse_push!(fb, Small(1));
+    fuse_push!(fb, Big(2));
+    fuse_push!(fb, Small(3));
+    fuse_push!(fb, Big(4));
+
+    let [a, b, c] = fb.get_disjoint_mut([3, 0, 1]).unwrap();
+    a.set(b.value() + c.value());
+    b.set(10);
+    assert_eq!(
+        fb.get_disjoint_mut([0, 4]).err(),
+        Some(GetDisjointMutError::IndexOutOfBounds)
+    );
+    assert_eq!(
+        fb.get_disjoint_mut([2, 1, 2]).err(),
+        Some(GetDisjointMutError::OverlappingIndices)
+    );
+
+    let (mut left, mut right) = fb.split_at_mut(2);
+    assert_eq!((left.len(), right.len()), (2, 2));
+    right[0].set(left[1].value() * 2);
+    left[1].set(right[1].value());
+    let (first, mut rest) = left.split_first_mut().unwrap();
+    first.set(first.value() + rest.last_mut().unwrap().value());
+
+    let (last, rest) = fb.split_last_mut().unwrap();
+    last.set(rest.iter().map(|v| v.value()).sum());
+    let (first, rest) = fb.split_first_mut().unwrap();
+    assert_eq!(rest.len(), 3);
+    first.set(first.value() + 1);
+    assert!(fb.split_at_mut(4).1.split_last_mut().is_none());
+
+    let values: Vec<_> = fb.iter().map(|v| v.value()).collect();
+    assert_eq!(values, [14, 3, 4, 20]);
+}
+
+#[test]
+fn chunks_windows_pairs() {
+    trait Node: Debug {
+        fn process(&mut self, input: u32) -> u32;
+        fn output(&self) -> u32;
+    }
+    #[derive(Debug)]
+    struct Gain(u8, u32);
+    #[derive(Debug)]
+    struct Offset(u64, u32);
+    impl Node for Gain {
+        fn process(&mut self, input: u32) -> u32 {
+            self.1 = input * u32::from(self.0);
+            self.1
+        }
+        fn output(&self) -> u32 {
+            self.1
+        }
+    }
+    impl Node for Offset {
+        fn process(&mut self, input: u32) -> u32 {
+            self.1 = input + self.0 as u32;
+            self.1
+        }
+        fn output(&self) -> u32 {
+            self.1
+        }
+    }
+
+    let mut fb = FuseBox::<dyn Node>::default();
+    fuse_push!(fb, Gain(2, 1));
+    fuse_push!(fb, Offset(3, 0));
+    fuse_push!(fb, Gain(4, 0));
+    fuse_push!(fb, Offset(5, 0));
+    fuse_push!(fb, Gain(6, 0));
+
+    let mut pairs = fb.pairs_mut();
+    assert_eq!(pairs.len(), 4);
+    while let Some((a, b)) = pairs.next() {
+        b.process(a.output());
+    }
+    let outputs: Vec<_> = fb.iter().map(|v| v.output()).collect();
+    assert_eq!(outputs, [1, 4, 16, 21, 126]);
+
+    let sums: Vec<u32> = fb
+        .windows(3)
+        .map(|w| w.iter().map(|v| v.output()).sum())
+        .collect();
+    assert_eq!(sums, [21, 41, 163]);
+    assert_eq!(fb.windows(6).len(), 0);
+    assert_eq!(fb.windows(2).next_back().unwrap().len(), 2);
+
+    let lens: Vec<_> = fb.chunks(2).map(|c| c.len()).collect();
+    assert_eq!(lens, [2, 2, 1]);
+    for mut chunk in fb.chunks_mut(2) {
+        let input = chunk.first().unwrap().output();
+        if let Some(last) = chunk.get_mut(1) {
+            last.process(input * 2);
+        }
+    }
+    let outputs: Vec<_> = fb.iter().map(|v| v.output()).collect();
+    assert_eq!(outputs, [1, 5, 16, 37, 126]);
+
+    let mut rest = fb.slice_mut(3..);
+    let mut pairs = rest.pairs_mut();
+    assert!(pairs.next().is_some());
+    assert!(pairs.next().is_none());
+    assert!(pairs.is_empty());
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn handles() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+    let a = fb.push_handle(1u8);
+    let b = fb.push_handle([2u64; 2]);
+    fuse_push!(fb, "three");
+    let c = fb.push_handle(String::from("four"));
+
+    fb[a] += 10;
+    fb[b][1] = 20;
+    fb[c].push('!');
+    assert_eq!(fb[a], 11);
+    assert_eq!(b.index(), 1);
+    assert_eq!(format!("{:?}", fb.dyn_ref(b)), "[2, 20]");
+    assert_eq!(format!("{:?}", fb.dyn_mut(c)), "\"four!\"");
+
+    // Other boxes never accept these handles
+    let mut other = FuseBox::<dyn Debug>::default();
+    assert!(!other.is_valid(a));
+    other.push_handle(0u8);
+    assert!(!other.is_valid(a));
+
+    // Appending keeps handles, moving elements around does not
+    fuse_push!(fb, 5u8);
+    fb.append(&mut other);
+    assert!(fb.is_valid(a) && fb.is_valid(c));
+    fb.swap(0, 1);
+    assert!(!fb.is_valid(a) && !fb.is_valid(b));
+
+    let d = fb.push_handle(6u32);
+    assert!(fb.is_valid(d));
+    assert_ne!(d.stamp, a.stamp);
+    fb.retain(|_| true);
+    assert!(!fb.is_valid(d));
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+#[should_panic = "invalid handle"]
+fn stale_handle() {
+    let mut fb = FuseBox::<dyn Debug>::default();
+    let a = fb.push_handle(1u8);
+    fuse_push!(fb, 2u8);
+    drop(fb.take(1));
+    let _ = fb[a];
+}
+
+#[test]
+fn slab() {
+    let drops = Rc::new(Cell::new(0));
+    let mut slab = FuseSlab::<dyn Debug>::default();
+    let a = fuse_push!(slab, 1u8);
+    let b = fuse_push!(slab, Counted(drops.clone()));
+    let c = fuse_push!(slab, 2u32);
+    assert_eq!(slab.len(), 3);
+
+    assert!(slab.remove(b));
+    assert!(!slab.remove(b));
+    assert_eq!(drops.get(), 1);
+    assert!(slab.get(b).is_none() && !slab.contains(b));
+
+    // Too big for the hole left by Counted, appended instead
+    let d = fuse_push!(slab, 3u128);
+    assert_eq!(d.index(), 3);
+    // Fits, reuses the slot but never answers to the old key
+    let e = fuse_push!(slab, 4u16);
+    assert_eq!(e.index(), b.index());
+    assert!(slab.get(b).is_none());
+    assert_eq!(format!("{:?}", &slab[c]), "2");
+
+    let printed: Vec<_> = slab
+        .iter()
+        .map(|(k, v)| (k.index(), format!("{v:?}")))
+        .collect();
+    assert_eq!(
+        printed,
+        [
+            (0, "1".into()),
+            (1, "4".into()),
+            (2, "2".into()),
+            (3, "3".into())
+        ]
+    );
+
+    assert_eq!(format!("{:?}", slab.take(a).unwrap()), "1");
+    assert!(slab.take(a).is_none());
+    let f = fuse_push!(slab, Counted(drops.clone()));
+    assert_ne!(f.index(), a.index());
+    for (_, v) in slab.iter_mut() {
+        let _ = format!("{v:?}");
+    }
+    assert_eq!(slab.len(), 4);
+    drop(slab);
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn slab_foreign_key() {
+    let mut a = FuseSlab::<dyn Debug>::new();
+    let mut b = FuseSlab::<dyn Debug>::new();
+    let in_a = fuse_push!(a, "osc in A");
+    assert!(b.get(in_a).is_none());
+    let in_b = fuse_push!(b, "filter in B");
+    // Same index and generation, but a different slab
+    assert_eq!(in_a.index(), in_b.index());
+    assert_ne!(in_a, in_b);
+    assert!(b.get(in_a).is_none() && !b.contains(in_a));
+    assert!(!b.remove(in_a));
+    assert!(b.take(in_a).is_none());
+    assert_eq!(format!("{:?}", &b[in_b]), "\"filter in B\"");
+    assert_eq!(format!("{:?}", &a[in_a]), "\"osc in A\"");
+}
+
+#[test]
+fn slab_free_lists() {
+    let mut slab = FuseSlab::<dyn Debug>::new();
+    let a = fuse_push!(slab, [0u8; 9]);
+    let b = fuse_push!(slab, [0u8; 17]);
+    let c = fuse_push!(slab, 1u64);
+    slab.remove(b);
+    slab.remove(a);
+    assert_eq!(slab.iter().len(), 1);
+
+    // The hole at 0 needs no padding, so it's preferred over the older one
+    let d = fuse_push!(slab, 2u64);
+    assert_eq!(d.index(), a.index());
+    // The hole at 9 still fits one with padding
+    let e = fuse_push!(slab, 3u64);
+    assert_eq!(e.index(), b.index());
+    assert_eq!(fuse_push!(slab, 4u64).index(), 3);
+
+    slab.remove(c);
+    let iter = slab.iter_mut();
+    assert_eq!(iter.len(), 3);
+    let printed: Vec<_> = iter.map(|(k, v)| (k.index(), format!("{v:?}"))).collect();
+    assert_eq!(printed, [(0, "2".into()), (1, "3".into()), (3, "4".into())]);
+}
+
+#[test]
+fn slab_no_fitting_hole() {
+    let mut slab = FuseSlab::<dyn Debug>::new();
+    let a = fuse_push!(slab, [0u8; 4]);
+    let b = fuse_push!(slab, [1u8; 16]);
+    let c = fuse_push!(slab, [2u8; 4]);
+    fuse_push!(slab, 3u8);
+    slab.remove(a);
+    slab.remove(b);
+    slab.remove(c);
+
+    // Bigger than every hole, appended
+    assert_eq!(fuse_push!(slab, [4u8; 32]).index(), 4);
+    assert_eq!(fuse_push!(slab, [5u8; 16]).index(), b.index());
+    // The 16 byte hole is gone, so only 4 byte holes are left
+    assert_eq!(fuse_push!(slab, [6u8; 8]).index(), 5);
+    assert_eq!(fuse_push!(slab, [7u8; 4]).index(), c.index());
+    assert_eq!(fuse_push!(slab, [8u8; 4]).index(), a.index());
+    assert_eq!(fuse_push!(slab, [9u8; 4]).index(), 6);
+    assert_eq!(slab.len(), 7);
+}
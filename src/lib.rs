@@ -1,7 +1,6 @@
-#![feature(ptr_metadata)]
-#![feature(unsize)]
-#![feature(core_intrinsics)]
-#![feature(allocator_api)]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(feature = "nightly", feature(ptr_metadata))]
+#![cfg_attr(feature = "nightly", feature(unsize))]
 #![warn(clippy::dbg_macro)]
 #![warn(clippy::all)]
 
@@ -11,20 +10,62 @@
 //!
 //! # Why?
 //!
-//! This avoids extra indirection of [`Vec<Box<dyn Trait>>`]
+//! This avoids extra indirection of `Vec<Box<dyn Trait>>`
 //!
 //! # Usage
 //!
 //! ```
 //! # use std::fmt::Debug;
-//! # use fusebox::FuseBox;
 //! # #[derive(Debug)]
 //! # struct MyStruct {}
+//! # #[cfg(all(feature = "alloc", feature = "nightly"))] {
+//! # use fusebox::FuseBox;
 //! let value = MyStruct {};
 //! let mut fb = FuseBox::<dyn Debug>::default();
 //! fb.push(value);
+//! # }
+//! ```
+//!
+//! # Stable Rust
+//!
+//! Without the default `nightly` feature, pointer metadata can only be captured from a concrete
+//! type. The generic `push`, `push_handle` and `replace` methods are unavailable, use their
+//! `_coerce` variants or [`fuse_push!`] instead. APIs taking `Box<Dyn>` or unsizing implicitly
+//! are unavailable too: `push_box`, `extend_boxed`, and the `From<Vec<Box<Dyn>>>`, `Extend`
+//! and `FromIterator` impls.
+//!
+//! ```
+//! # #[cfg(feature = "alloc")] {
+//! # use std::fmt::Debug;
+//! # use fusebox::{fuse_push, FuseBox};
+//! let mut fb = FuseBox::<dyn Debug>::default();
+//! fuse_push!(fb, 42u8);
+//! # }
 //! ```
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod fuse;
 
-pub use fuse::FuseBox;
+pub use fuse::array::ArrayFuseBox;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub use fuse::{handle::Handle, slab::FuseSlab};
+#[cfg(feature = "alloc")]
+pub use fuse::{small::SmallFuseBox, stride::StrideFuseBox, FuseBox};
+
+/// Pushes a value into any fusebox collection without relying on the `nightly` feature.
+///
+/// Expands to a call to `push_coerce` with the unsizing coercion as the `coerce` argument,
+/// and evaluates to whatever `push_coerce` returns.
+#[macro_export]
+macro_rules! fuse_push {
+    ($fb:expr, $v:expr) => {{
+        let fb = &mut $fb;
+        let v = $v;
+        // Safety: the closure is the unsizing coercion
+        unsafe { fb.push_coerce(v, |p| p) }
+    }};
+}